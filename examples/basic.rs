@@ -1,6 +1,5 @@
 extern crate ds_store;
 
-use std::collections::HashMap;
 use std::{io::Read, fs::File};
 use ds_store::{DsStore, RecordValue};
 
@@ -22,7 +21,7 @@ fn main() {
             return;
         }
     };
-    let records: &HashMap<String, HashMap<&str, RecordValue>> = store.contents();
+    let records: Vec<(&str, &str, &RecordValue)> = store.records();
     records.iter().for_each(|r| println!("{:?}", r));
     println!("printed {:?} records", records.len());
 }
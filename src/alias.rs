@@ -0,0 +1,495 @@
+use byteorder::{ByteOrder, BigEndian};
+use crate::Error;
+use crate::allocator::Timestamp;
+
+/// Characters 0x80 to 0xFF of Mac OS Roman, the encoding of names in the fixed-size part of Alias records.
+const MAC_ROMAN_HIGH: &str = "ÄÅÇÉÑÖÜáàâäãåçéèêëíìîïñóòôöõúùûü†°¢£§•¶ß®©™´¨≠ÆØ∞±≤≥¥µ∂∑∏π∫ªºΩæø\
+                              ¿¡¬√ƒ≈∆«»…\u{a0}ÀÃÕŒœ–—“”‘’÷◊ÿŸ⁄€‹›ﬁﬂ‡·‚„‰ÂÊÁËÈÍÎÏÌÓÔ\u{f8ff}ÒÚÛÙıˆ˜¯˘˙˚¸˝˛ˇ";
+
+fn decode_mac_roman(data: &[u8]) -> String {
+    data.iter()
+        .map(|&b| if b < 0x80 { b as char } else { MAC_ROMAN_HIGH.chars().nth((b - 0x80) as usize).unwrap_or('?') })
+        .collect()
+}
+
+/// Characters that Mac OS Roman cannot represent become '?'.
+fn encode_mac_roman(s: &str) -> Vec<u8> {
+    s.chars()
+        .map(|c| if (c as u32) < 0x80 {
+            c as u8
+        } else {
+            MAC_ROMAN_HIGH.chars().position(|high| high == c).map_or(b'?', |i| 0x80 + i as u8)
+        })
+        .collect()
+}
+
+/// A classic Mac OS Alias record, pointing to a file or folder on a volume.
+/// Finder stores one in the "pict" record of a directory with a picture background,
+/// and in the "backgroundImageAlias" entry of its "icvp" record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alias {
+    /// Application-specific data stored before the record, usually zeros.
+    pub app_info: [u8; 4],
+    /// 2 or 3. Version 3 stores the names only in the tagged extras.
+    pub version: u16,
+    pub kind: AliasKind,
+    pub volume: AliasVolume,
+    pub target: AliasTarget,
+    /// The tagged extras, in the order they are stored.
+    pub extras: Vec<AliasExtra>,
+}
+
+/// What an alias points to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AliasKind {
+    /// represented as 0
+    File,
+    /// represented as 1
+    Folder,
+    /// Any other kind, as it is represented.
+    Other(i16),
+}
+
+impl AliasKind {
+    pub fn from_code(code: i16) -> AliasKind {
+        match code {
+            0 => AliasKind::File,
+            1 => AliasKind::Folder,
+            other => AliasKind::Other(other),
+        }
+    }
+
+    pub fn code(self) -> i16 {
+        match self {
+            AliasKind::File => 0,
+            AliasKind::Folder => 1,
+            AliasKind::Other(code) => code,
+        }
+    }
+}
+
+/// The volume an alias points into.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AliasVolume {
+    /// The Unicode volume name if the alias has one, otherwise the Mac OS Roman one.
+    pub name: String,
+    /// The high resolution date if the alias has one, otherwise the date in whole seconds.
+    pub created: Timestamp,
+    /// The file system, "H+" for HFS+. Version 2 only stores the first two bytes.
+    pub fs_type: [u8; 4],
+    /// 0 for a fixed disk, 1 for a network disk, 5 for an ejectable disk such as a disk image.
+    pub disk_type: i16,
+    pub attributes: u32,
+}
+
+/// The file or folder an alias points to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AliasTarget {
+    /// The Unicode file name if the alias has one, otherwise the Mac OS Roman one.
+    pub name: String,
+    /// The catalog node ID of the target.
+    pub id: u32,
+    /// The catalog node ID of the directory containing the target.
+    pub parent_id: u32,
+    /// The high resolution date if the alias has one, otherwise the date in whole seconds.
+    pub created: Timestamp,
+    /// The file type code. Only stored in version 2.
+    pub file_type: [u8; 4],
+    /// The creator code. Only stored in version 2.
+    pub creator: [u8; 4],
+    /// How many directories up from the alias to the closest common ancestor, -1 if unknown.
+    /// Only stored in version 2.
+    pub levels_from: i16,
+    /// How many directories down from the closest common ancestor to the target, -1 if unknown.
+    /// Only stored in version 2.
+    pub levels_to: i16,
+}
+
+/// A tagged extra field, stored after the fixed-size part of an alias.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AliasExtra {
+    /// tag 0: the name of the directory containing the target, in Mac OS Roman.
+    ParentName(String),
+    /// tag 1: the catalog node IDs of the directories from the target's parent up to the volume root.
+    CnidPath(Vec<u32>),
+    /// tag 2: the path of the target as "Volume:directory:file", in Mac OS Roman.
+    CarbonPath(String),
+    /// tag 14: the name of the target, in UTF-16.
+    UnicodeFileName(String),
+    /// tag 15: the name of the volume, in UTF-16.
+    UnicodeVolumeName(String),
+    /// tag 16
+    VolumeCreated(Timestamp),
+    /// tag 17
+    TargetCreated(Timestamp),
+    /// tag 18: the POSIX path of the target, relative to the volume, like "/.background/bg.png".
+    PosixPath(String),
+    /// tag 19: where the volume was mounted, like "/Volumes/Installer".
+    PosixMountPoint(String),
+    /// Any other tag, or a known tag whose data could not be decoded, with its data.
+    Other(i16, Vec<u8>),
+}
+
+impl AliasExtra {
+    fn decode(tag: i16, data: &[u8]) -> Option<AliasExtra> {
+        match tag {
+            0 => Some(AliasExtra::ParentName(decode_mac_roman(data))),
+            1 if data.len() % 4 == 0 => Some(AliasExtra::CnidPath(data.chunks(4).map(BigEndian::read_u32).collect())),
+            2 => Some(AliasExtra::CarbonPath(decode_mac_roman(data))),
+            14 => decode_unicode_name(data).map(AliasExtra::UnicodeFileName),
+            15 => decode_unicode_name(data).map(AliasExtra::UnicodeVolumeName),
+            16 if data.len() == 8 => Some(AliasExtra::VolumeCreated(Timestamp::from_ticks(BigEndian::read_i64(data)))),
+            17 if data.len() == 8 => Some(AliasExtra::TargetCreated(Timestamp::from_ticks(BigEndian::read_i64(data)))),
+            18 => String::from_utf8(data.to_vec()).ok().map(AliasExtra::PosixPath),
+            19 => String::from_utf8(data.to_vec()).ok().map(AliasExtra::PosixMountPoint),
+            _ => None,
+        }
+    }
+}
+
+/// A character count, followed by that many UTF-16 code units.
+fn decode_unicode_name(data: &[u8]) -> Option<String> {
+    if data.len() < 2 || data.len() != 2 + 2 * BigEndian::read_u16(data) as usize {
+        return None;
+    }
+    let units: Vec<u16> = data[2..].chunks(2).map(BigEndian::read_u16).collect();
+    String::from_utf16(&units).ok()
+}
+
+impl Alias {
+    /// An alias to the file at `path` on the volume named `volume_name`, like `.background/bg.png`,
+    /// created at `created` on a volume created at `volume_created`. Unknown dates are stored as 0.
+    /// Catalog node IDs cannot be known without the volume, so they are left at 0,
+    /// and Finder finds the file by the paths and names in the extras instead.
+    ///
+    /// The alias is for a file on an ejectable HFS+ volume, such as a disk image mounted at `/Volumes/<volume_name>`.
+    pub fn new(volume_name: &str, path: &str, volume_created: Option<Timestamp>, created: Option<Timestamp>) -> Alias {
+        let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+        let target_name = components.last().map_or("", |name| name);
+        let mut extras = Vec::new();
+        if components.len() > 1 {
+            extras.push(AliasExtra::ParentName(components[components.len() - 2].to_string()));
+        }
+        // Colons can't appear in Carbon path components, and are shown as slashes instead.
+        let carbon_components: Vec<String> = components.iter().map(|c| c.replace(':', "/")).collect();
+        extras.push(AliasExtra::CarbonPath(format!("{}:{}", volume_name, carbon_components.join(":"))));
+        extras.push(AliasExtra::UnicodeFileName(target_name.to_string()));
+        extras.push(AliasExtra::UnicodeVolumeName(volume_name.to_string()));
+        if let Some(date) = volume_created {
+            extras.push(AliasExtra::VolumeCreated(date));
+        }
+        if let Some(date) = created {
+            extras.push(AliasExtra::TargetCreated(date));
+        }
+        extras.push(AliasExtra::PosixPath(format!("/{}", components.join("/"))));
+        extras.push(AliasExtra::PosixMountPoint(format!("/Volumes/{}", volume_name)));
+
+        Alias {
+            app_info: [0; 4],
+            version: 2,
+            kind: AliasKind::File,
+            volume: AliasVolume {
+                name: volume_name.to_string(),
+                created: volume_created.unwrap_or_else(|| Timestamp::from_ticks(0)),
+                fs_type: *b"H+\0\0",
+                disk_type: 5,
+                attributes: 0,
+            },
+            target: AliasTarget {
+                name: target_name.to_string(),
+                id: 0,
+                parent_id: 0,
+                created: created.unwrap_or_else(|| Timestamp::from_ticks(0)),
+                file_type: [0; 4],
+                creator: [0; 4],
+                levels_from: -1,
+                levels_to: -1,
+            },
+            extras,
+        }
+    }
+
+    /// The POSIX path of the target relative to its volume, if the alias stores it.
+    pub fn posix_path(&self) -> Option<&str> {
+        self.extras.iter().find_map(|extra| match *extra {
+            AliasExtra::PosixPath(ref path) => Some(path.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Where the volume was mounted when the alias was made, if the alias stores it.
+    pub fn posix_mount_point(&self) -> Option<&str> {
+        self.extras.iter().find_map(|extra| match *extra {
+            AliasExtra::PosixMountPoint(ref path) => Some(path.as_str()),
+            _ => None,
+        })
+    }
+
+    /// The full POSIX path of the target, if the alias stores both its mount point and its path.
+    pub fn full_posix_path(&self) -> Option<String> {
+        let mount_point = self.posix_mount_point()?.trim_end_matches('/');
+        let path = self.posix_path()?;
+        Some(format!("{}/{}", mount_point, path.trim_start_matches('/')))
+    }
+
+    /// The path of the target as "Volume:directory:file", if the alias stores it.
+    pub fn carbon_path(&self) -> Option<&str> {
+        self.extras.iter().find_map(|extra| match *extra {
+            AliasExtra::CarbonPath(ref path) => Some(path.as_str()),
+            _ => None,
+        })
+    }
+}
+
+/// An alias record value, along with the bytes it was read from.
+/// Values read from a file are written back as those exact bytes, other values are written with `write`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AliasRecord<'a> {
+    value: Alias,
+    source: Option<&'a [u8]>,
+}
+
+impl<'a> AliasRecord<'a> {
+    pub fn new(value: Alias) -> AliasRecord<'a> {
+        AliasRecord {value, source: None}
+    }
+
+    /// Parse an Alias record, keeping `data` to write back.
+    pub fn parse(data: &'a [u8]) -> Result<AliasRecord<'a>, Error<'a>> {
+        Ok(AliasRecord {value: parse(data)?, source: Some(data)})
+    }
+
+    pub fn value(&self) -> &Alias {
+        &self.value
+    }
+
+    pub fn into_value(self) -> Alias {
+        self.value
+    }
+
+    /// The bytes the value was read from, if it was read from a file.
+    pub fn source(&self) -> Option<&'a [u8]> {
+        self.source
+    }
+}
+
+/// Reads big-endian values from the front of a slice.
+struct Cursor<'d>(&'d [u8]);
+
+impl<'d> Cursor<'d> {
+    fn take<'a>(&mut self, len: usize) -> Result<&'d [u8], Error<'a>> {
+        if self.0.len() < len {
+            return Err(Error::NotEnoughData);
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn take_array<'a, const N: usize>(&mut self) -> Result<[u8; N], Error<'a>> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn i16<'a>(&mut self) -> Result<i16, Error<'a>> {
+        Ok(BigEndian::read_i16(self.take(2)?))
+    }
+
+    fn u16<'a>(&mut self) -> Result<u16, Error<'a>> {
+        Ok(BigEndian::read_u16(self.take(2)?))
+    }
+
+    fn u32<'a>(&mut self) -> Result<u32, Error<'a>> {
+        Ok(BigEndian::read_u32(self.take(4)?))
+    }
+
+    fn i64<'a>(&mut self) -> Result<i64, Error<'a>> {
+        Ok(BigEndian::read_i64(self.take(8)?))
+    }
+
+    /// A length byte followed by the string, in a field of `size` bytes.
+    fn pascal_string<'a>(&mut self, size: usize) -> Result<String, Error<'a>> {
+        let field = self.take(size)?;
+        match field.get(1..1 + field[0] as usize) {
+            Some(s) => Ok(decode_mac_roman(s)),
+            None => Err(Error::BadData("Alias record has a name longer than its field.")),
+        }
+    }
+}
+
+fn seconds(seconds: u32) -> Timestamp {
+    Timestamp::from_ticks(i64::from(seconds) * Timestamp::TICKS_PER_SECOND)
+}
+
+/// Parse an Alias record, version 2 or 3.
+///
+/// Both versions start with 4 bytes of application-specific data, the size of the record and its version.
+/// Then comes a fixed-size part, and tagged extras, each a tag, a length and the data padded to an even length,
+/// until the tag -1.
+pub fn parse<'a>(data: &[u8]) -> Result<Alias, Error<'a>> {
+    let mut cursor = Cursor(data);
+    let app_info = cursor.take_array()?;
+    let size = cursor.u16()? as usize;
+    let version = cursor.u16()?;
+    if size < 8 || size > data.len() {
+        return Err(Error::BadData("Alias record size doesn't match its data."));
+    }
+    cursor = Cursor(&data[8..size]);
+
+    let kind = AliasKind::from_code(cursor.i16()?);
+    let (mut volume, mut target) = match version {
+        2 => {
+            let volume_name = cursor.pascal_string(28)?;
+            let volume_created = seconds(cursor.u32()?);
+            let fs_type: [u8; 2] = cursor.take_array()?;
+            let disk_type = cursor.i16()?;
+            let parent_id = cursor.u32()?;
+            let target_name = cursor.pascal_string(64)?;
+            let id = cursor.u32()?;
+            let created = seconds(cursor.u32()?);
+            let creator = cursor.take_array()?;
+            let file_type = cursor.take_array()?;
+            let levels_from = cursor.i16()?;
+            let levels_to = cursor.i16()?;
+            let attributes = cursor.u32()?;
+            cursor.take(2 + 10)?; // the file system ID, and reserved bytes.
+            (AliasVolume {name: volume_name, created: volume_created, fs_type: [fs_type[0], fs_type[1], 0, 0], disk_type, attributes},
+             AliasTarget {name: target_name, id, parent_id, created, file_type, creator, levels_from, levels_to})
+        },
+        3 => {
+            let volume_created = Timestamp::from_ticks(cursor.i64()?);
+            let fs_type = cursor.take_array()?;
+            let disk_type = cursor.i16()?;
+            let parent_id = cursor.u32()?;
+            let id = cursor.u32()?;
+            let created = Timestamp::from_ticks(cursor.i64()?);
+            let attributes = cursor.u32()?;
+            cursor.take(14)?; // reserved bytes.
+            (AliasVolume {name: String::new(), created: volume_created, fs_type, disk_type, attributes},
+             AliasTarget {name: String::new(), id, parent_id, created, file_type: [0; 4], creator: [0; 4], levels_from: -1, levels_to: -1})
+        },
+        _ => return Err(Error::BadData("Only version 2 and 3 Alias records are supported.")),
+    };
+
+    let mut extras = Vec::new();
+    loop {
+        let tag = cursor.i16()?;
+        let len = cursor.u16()? as usize;
+        if tag == -1 {
+            break;
+        }
+        let data = cursor.take(len)?;
+        if len % 2 == 1 {
+            cursor.take(1)?;
+        }
+        extras.push(AliasExtra::decode(tag, data).unwrap_or_else(|| AliasExtra::Other(tag, data.to_vec())));
+    }
+
+    for extra in &extras {
+        match *extra {
+            AliasExtra::UnicodeVolumeName(ref name) => volume.name = name.clone(),
+            AliasExtra::UnicodeFileName(ref name) => target.name = name.clone(),
+            AliasExtra::VolumeCreated(date) => volume.created = date,
+            AliasExtra::TargetCreated(date) => target.created = date,
+            _ => {},
+        }
+    }
+    Ok(Alias {app_info, version, kind, volume, target, extras})
+}
+
+/// Whole seconds since the Mac epoch, as stored in version 2, clamped to what fits.
+fn to_seconds(timestamp: Timestamp) -> u32 {
+    timestamp.ticks().div_euclid(Timestamp::TICKS_PER_SECOND).clamp(0, i64::from(u32::MAX)) as u32
+}
+
+/// A length byte followed by the Mac OS Roman string, truncated to fit and padded with zeros to `size` bytes.
+fn write_pascal_string(data: &mut Vec<u8>, s: &str, size: usize) {
+    let mut encoded = encode_mac_roman(s);
+    encoded.truncate(size - 1);
+    data.push(encoded.len() as u8);
+    data.extend_from_slice(&encoded);
+    data.resize(data.len() + size - 1 - encoded.len(), 0);
+}
+
+fn write_unicode_name(name: &str) -> Vec<u8> {
+    let units: Vec<u16> = name.encode_utf16().collect();
+    let mut data = vec![0; 2 + 2 * units.len()];
+    BigEndian::write_u16(&mut data, units.len() as u16);
+    BigEndian::write_u16_into(&units, &mut data[2..]);
+    data
+}
+
+impl AliasExtra {
+    fn encode(&self) -> (i16, Vec<u8>) {
+        match *self {
+            AliasExtra::ParentName(ref name) => (0, encode_mac_roman(name)),
+            AliasExtra::CnidPath(ref ids) => {
+                let mut data = vec![0; 4 * ids.len()];
+                BigEndian::write_u32_into(ids, &mut data);
+                (1, data)
+            },
+            AliasExtra::CarbonPath(ref path) => (2, encode_mac_roman(path)),
+            AliasExtra::UnicodeFileName(ref name) => (14, write_unicode_name(name)),
+            AliasExtra::UnicodeVolumeName(ref name) => (15, write_unicode_name(name)),
+            AliasExtra::VolumeCreated(date) => (16, date.ticks().to_be_bytes().to_vec()),
+            AliasExtra::TargetCreated(date) => (17, date.ticks().to_be_bytes().to_vec()),
+            AliasExtra::PosixPath(ref path) => (18, path.as_bytes().to_vec()),
+            AliasExtra::PosixMountPoint(ref path) => (19, path.as_bytes().to_vec()),
+            AliasExtra::Other(tag, ref data) => (tag, data.clone()),
+        }
+    }
+}
+
+/// Write an Alias record, in the layout of its version: 3 if it is 3, otherwise 2.
+/// Names that don't fit the fixed-size part of version 2 are truncated there, the extras keep them whole.
+pub fn write(alias: &Alias) -> Vec<u8> {
+    let version: u16 = if alias.version == 3 { 3 } else { 2 };
+    let mut data = Vec::new();
+    data.extend_from_slice(&alias.app_info);
+    data.extend_from_slice(&[0, 0]); // the size, once it is known.
+    data.extend_from_slice(&version.to_be_bytes());
+    data.extend_from_slice(&alias.kind.code().to_be_bytes());
+
+    let (volume, target) = (&alias.volume, &alias.target);
+    if version == 2 {
+        write_pascal_string(&mut data, &volume.name, 28);
+        data.extend_from_slice(&to_seconds(volume.created).to_be_bytes());
+        data.extend_from_slice(&volume.fs_type[..2]);
+        data.extend_from_slice(&volume.disk_type.to_be_bytes());
+        data.extend_from_slice(&target.parent_id.to_be_bytes());
+        write_pascal_string(&mut data, &target.name, 64);
+        data.extend_from_slice(&target.id.to_be_bytes());
+        data.extend_from_slice(&to_seconds(target.created).to_be_bytes());
+        data.extend_from_slice(&target.creator);
+        data.extend_from_slice(&target.file_type);
+        data.extend_from_slice(&target.levels_from.to_be_bytes());
+        data.extend_from_slice(&target.levels_to.to_be_bytes());
+        data.extend_from_slice(&volume.attributes.to_be_bytes());
+        data.extend_from_slice(&[0; 2 + 10]);
+    } else {
+        data.extend_from_slice(&volume.created.ticks().to_be_bytes());
+        data.extend_from_slice(&volume.fs_type);
+        data.extend_from_slice(&volume.disk_type.to_be_bytes());
+        data.extend_from_slice(&target.parent_id.to_be_bytes());
+        data.extend_from_slice(&target.id.to_be_bytes());
+        data.extend_from_slice(&target.created.ticks().to_be_bytes());
+        data.extend_from_slice(&volume.attributes.to_be_bytes());
+        data.extend_from_slice(&[0; 14]);
+    }
+
+    for extra in &alias.extras {
+        let (tag, value) = extra.encode();
+        data.extend_from_slice(&tag.to_be_bytes());
+        data.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        data.extend_from_slice(&value);
+        if value.len() % 2 == 1 {
+            data.push(0);
+        }
+    }
+    data.extend_from_slice(&[0xff, 0xff, 0, 0]);
+
+    let size = data.len() as u16;
+    BigEndian::write_u16(&mut data[4..6], size);
+    data
+}
@@ -1,22 +1,111 @@
 
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use byteorder::{ByteOrder, BigEndian};
+use chrono::TimeZone;
 use crate::Error;
+use crate::plist::PlistRecord;
+use crate::alias::{Alias, AliasRecord};
+use crate::bookmark::BookmarkRecord;
+use crate::hfs::fast_unicode_compare;
 
 // TODO verify `as usize` casts, or place them where they truly belong (where they are created, not when used).
 
 #[derive(Debug)]
 pub struct Directory<'a> {
-    num_internals: u32,
-    num_nodes: u32,
-    num_records: u32,
+    /// The block of the root node of the B-Tree.
+    pub root_node: u32,
+    /// The number of internal levels of the B-Tree, 0 when the root is a leaf.
+    pub num_internals: u32,
+    /// The number of nodes in the B-Tree.
+    pub num_nodes: u32,
+    /// The number of records in the B-Tree.
+    pub num_records: u32,
 
-    /// A map from file/directory to its information
-    pub contents: HashMap<String, HashMap<&'a str, RecordValue<'a>>>,
+    /// A map from file/directory to its information, ordered by filename and code,
+    /// so iterating over it always gives the same order.
+    pub contents: BTreeMap<String, BTreeMap<&'a str, RecordValue<'a>>>,
+    /// The B-Tree nodes, as they were laid out in the file.
+    pub nodes: Vec<NodeLayout<'a>>,
+    /// The records stored more than once in the B-Tree, ordered like `contents`.
+    /// `contents` only holds the first occurrence of each.
+    pub duplicates: Vec<DuplicateRecord<'a>>,
 }
 
-// TODO: Better strongly type these. Instead of having so many slices, parse more. Also, PList variant.
+impl<'a> Directory<'a> {
+    /// Create a directory from its contents. The B-Tree layout is only known once it is written.
+    pub fn new(contents: BTreeMap<String, BTreeMap<&'a str, RecordValue<'a>>>) -> Directory<'a> {
+        Directory {root_node: 0, num_internals: 0, num_nodes: 0, num_records: 0, contents, nodes: Vec::new(), duplicates: Vec::new()}
+    }
+
+    /// The (filename, code) of every record in `nodes`, in B-Tree order, duplicates included.
+    /// Empty for a directory that was not read from a file.
+    pub fn stored_keys(&self) -> Vec<(&str, &'a str)> {
+        let nodes: BTreeMap<u32, &NodeLayout<'a>> = self.nodes.iter().map(|node| (node.block_id, node)).collect();
+        let mut keys = Vec::with_capacity(self.num_records as usize);
+        collect_keys(self.root_node, &nodes, &mut keys);
+        keys
+    }
+}
+
+/// Appends the keys of the subtree at `block_id` to `keys`: each child's records come before the record that points to it.
+fn collect_keys<'a, 'b>(block_id: u32, nodes: &BTreeMap<u32, &'b NodeLayout<'a>>, keys: &mut Vec<(&'b str, &'a str)>) {
+    if let Some(node) = nodes.get(&block_id) {
+        for &(child, ref file_name, code, _) in &node.records {
+            if child != 0 {
+                collect_keys(child, nodes, keys);
+            }
+            keys.push((file_name.as_str(), code));
+        }
+        if node.rightmost_child != 0 {
+            collect_keys(node.rightmost_child, nodes, keys);
+        }
+    }
+}
+
+/// What to do with a record that has the same filename and code as one read before it.
+/// Finder never writes those, but corrupted or hand-made files can have them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Duplicates {
+    /// Fail with `Error::DuplicateRecord`.
+    Reject,
+    /// Keep the first occurrence in `Directory::contents`, and every occurrence in `Directory::duplicates`.
+    Keep,
+}
+
+/// Where a record is stored in the B-Tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RecordLocation {
+    /// The block of the node holding the record.
+    pub block_id: u32,
+    /// The position of the record within that node.
+    pub index: usize,
+}
+
+/// A filename and code stored more than once in the B-Tree.
+#[derive(Debug, Clone)]
+pub struct DuplicateRecord<'a> {
+    pub file_name: String,
+    pub code: &'a str,
+    /// Every occurrence with the value it was read as, in B-Tree order.
+    /// The first one is the one in `Directory::contents`.
+    pub occurrences: Vec<(RecordLocation, RecordValue<'a>)>,
+}
+
+/// A B-Tree node, as it was laid out in the file.
 #[derive(Debug)]
+pub struct NodeLayout<'a> {
+    /// The block the node is stored in.
+    pub block_id: u32,
+    /// The block of the rightmost child, 0 for leaves.
+    pub rightmost_child: u32,
+    /// The (child block, filename, record type, source) of each record, in the order they are stored.
+    /// The child block is 0 for leaves, and the source is the bytes of the record type, data type and value as they were read.
+    pub records: Vec<(u32, String, &'a str, &'a [u8])>,
+}
+
+// TODO: Better strongly type these. Instead of having so many slices, parse more.
+#[derive(Debug, Clone)]
 pub enum RecordValue<'a> {
     Background(BackgroundType),
     Style(StyleType),
@@ -27,23 +116,63 @@ pub enum RecordValue<'a> {
     I32(i32),
     I64(i64),
     U32(u32),
-    DateTime(chrono::DateTime<chrono::Utc>),
+    DateTime(Timestamp),
+    IconLocation(IconLocation),
+    DesktopIconLocation(DesktopIconLocation),
+    LegacyIconView(LegacyIconViewOptions),
+    WindowInfo(WindowInfo),
+    FinderInfo(FinderInfo),
+    Plist(PlistRecord<'a>),
+    Alias(AliasRecord<'a>),
+    Bookmark(BookmarkRecord<'a>),
+    /// A record type this library does not know yet, with its value decoded by data type alone.
+    Unknown(&'a str, GenericValue<'a>),
 }
 
-#[derive(Debug)]
+/// A record value decoded only from the data type stored before it.
+#[derive(Debug, Clone)]
+pub enum GenericValue<'a> {
+    /// represented as "long" in the .DS_Store file.
+    Long(i32),
+    /// represented as "shor" in the .DS_Store file. Stored in 4 bytes, but only 16 bit.
+    Short(i16),
+    /// represented as "bool" in the .DS_Store file.
+    Bool(bool),
+    /// represented as "blob" in the .DS_Store file.
+    Blob(&'a [u8]),
+    /// represented as "type" in the .DS_Store file. A FourCharCode.
+    Type(&'a [u8]),
+    /// represented as "ustr" in the .DS_Store file.
+    UStr(String),
+    /// represented as "comp" in the .DS_Store file.
+    Comp(i64),
+    /// represented as "dutc" in the .DS_Store file.
+    DateTime(Timestamp),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum BackgroundType {
     // Parsed as: FourCharCode "DefB", followed by eight unknown bytes, probably garbage.
     Default,
     // Parsed as: FourCharCode "ClrB", followed by an RGB value in six bytes, followed by two unknown bytes.
     SolidColor(u16, u16, u16),
     // Parsed as: FourCharCode "PctB", followed by the the length of the blob stored in the 'pict' record,
-    // followed by four unknown bytes. The 'pict' record points to the actual background image.
-    Picture(u32),
+    // followed by four unknown bytes. The 'pict' record is an Alias pointing to the actual background image,
+    // which is filled in when the directory's records are read, if it could be parsed.
+    Picture(u32, Option<Alias>),
+}
+
+impl BackgroundType {
+    /// A picture background showing the file `alias` points to.
+    /// The directory's "pict" record must hold the same alias.
+    pub fn picture(alias: Alias) -> BackgroundType {
+        BackgroundType::Picture(crate::alias::write(&alias).len() as u32, Some(alias))
+    }
 }
 
 /// How a directory is viewed in the finder.
 /// Icon view, Column/Browser view, List view, and Cover Flow view.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum StyleType {
     /// represented as "icnv" in the .DS_Store file.
     Icon,
@@ -55,6 +184,209 @@ pub enum StyleType {
     CoverFlow,
 }
 
+/// Where an icon is placed in its directory's icon view, stored in "Iloc" records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IconLocation {
+    pub x: u32,
+    pub y: u32,
+    /// The 8 bytes after the position, usually `FFFFFFFF FFFF0000`. Their meaning is unknown.
+    pub unknown: [u8; 8],
+}
+
+impl IconLocation {
+    /// The bytes Finder usually writes after the position.
+    pub const DEFAULT_UNKNOWN: [u8; 8] = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0, 0];
+
+    pub fn new(x: u32, y: u32) -> IconLocation {
+        IconLocation {x, y, unknown: IconLocation::DEFAULT_UNKNOWN}
+    }
+}
+
+/// Icon view options from before Mac OS X 10.6, stored in "icvo" records.
+/// Newer versions of Finder use "icvp" instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LegacyIconViewOptions {
+    /// The older 18-byte layout: FourCharCode "icvo", eight unknown bytes (flags?),
+    /// the icon size and the arrangement FourCharCode.
+    Icvo { unknown: [u8; 8], icon_size: u16, arrangement: [u8; 4] },
+    /// The 26-byte layout: FourCharCode "icv4", the icon size, the arrangement FourCharCode,
+    /// the label position FourCharCode and twelve unknown bytes (flags?).
+    Icv4 { icon_size: u16, arrangement: [u8; 4], label_position: [u8; 4], unknown: [u8; 12] },
+}
+
+impl LegacyIconViewOptions {
+    /// The icon size in pixels.
+    pub fn icon_size(&self) -> u16 {
+        match *self {
+            LegacyIconViewOptions::Icvo { icon_size, .. } | LegacyIconViewOptions::Icv4 { icon_size, .. } => icon_size,
+        }
+    }
+
+    /// How icons are arranged, "none" or "grid".
+    pub fn arrangement(&self) -> &[u8; 4] {
+        match *self {
+            LegacyIconViewOptions::Icvo { ref arrangement, .. } | LegacyIconViewOptions::Icv4 { ref arrangement, .. } => arrangement,
+        }
+    }
+
+    /// Where labels are placed, "botm" or "rght". Only the "icv4" layout stores it.
+    pub fn label_position(&self) -> Option<&[u8; 4]> {
+        match *self {
+            LegacyIconViewOptions::Icvo { .. } => None,
+            LegacyIconViewOptions::Icv4 { ref label_position, .. } => Some(label_position),
+        }
+    }
+}
+
+/// A Finder window's position and view, stored in "fwi0" records.
+/// Newer versions of Finder use "bwsp" instead.
+/// The rectangle is in QuickDraw global coordinates, where y grows downwards from the top of the main screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowInfo {
+    pub top: i16,
+    pub left: i16,
+    pub bottom: i16,
+    pub right: i16,
+    /// The view style FourCharCode, as in "vstl" records.
+    pub view_style: [u8; 4],
+    /// The 4 bytes after the view style. Their meaning is unknown, maybe flags.
+    pub unknown: [u8; 4],
+}
+
+impl WindowInfo {
+    /// The view style, if it is one this library knows.
+    pub fn style(&self) -> Option<StyleType> {
+        match &self.view_style {
+            b"icnv" => Some(StyleType::Icon),
+            b"clmv" => Some(StyleType::ColumnBrowser),
+            b"Nlsv" => Some(StyleType::List),
+            b"Flwv" => Some(StyleType::CoverFlow),
+            _ => None,
+        }
+    }
+}
+
+/// Where an icon is placed on the desktop, stored in "dilc" records.
+///
+/// A negative coordinate is an offset in pixels from the right or bottom edge of the screen,
+/// so icons near those edges stay there when the resolution changes.
+/// Coordinates may also be stored as a percentage of the screen size, but which flag says so
+/// is not known, so `flags` is only kept as it was found and coordinates are always read as pixels.
+/// Everything else is kept as it was found too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DesktopIconLocation {
+    /// The first 4 bytes. Their meaning is unknown.
+    pub flags: u32,
+    /// The 4 bytes after the flags. Their meaning is unknown.
+    pub unknown: [u8; 4],
+    pub x: i32,
+    pub y: i32,
+    /// The 16 bytes after the position. Their meaning is unknown.
+    pub trailing: [u8; 16],
+}
+
+/// Which edge of the screen one coordinate of a `DesktopIconLocation` is measured from, and how far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DesktopCoordinate {
+    /// Pixels from the left or top edge of the screen.
+    FromStart(u32),
+    /// Pixels from the right or bottom edge of the screen, stored as a negative number.
+    FromEnd(u32),
+}
+
+impl DesktopCoordinate {
+    fn new(value: i32) -> DesktopCoordinate {
+        if value < 0 {
+            DesktopCoordinate::FromEnd(value.unsigned_abs())
+        } else {
+            DesktopCoordinate::FromStart(value as u32)
+        }
+    }
+
+    /// The coordinate in pixels from the left or top edge, on a screen `size` pixels wide or high.
+    pub fn absolute(self, size: u32) -> i64 {
+        match self {
+            DesktopCoordinate::FromStart(pixels) => i64::from(pixels),
+            DesktopCoordinate::FromEnd(pixels) => i64::from(size) - i64::from(pixels),
+        }
+    }
+}
+
+impl DesktopIconLocation {
+    pub fn horizontal(&self) -> DesktopCoordinate {
+        DesktopCoordinate::new(self.x)
+    }
+
+    pub fn vertical(&self) -> DesktopCoordinate {
+        DesktopCoordinate::new(self.y)
+    }
+
+    /// The position in pixels from the top left corner of a `width` by `height` screen.
+    pub fn absolute(&self, width: u32, height: u32) -> (i64, i64) {
+        (self.horizontal().absolute(width), self.vertical().absolute(height))
+    }
+}
+
+// TODO: say which versions of macOS write 40 and 48 byte "info" records, once there are samples of both to test with.
+/// The "info" record, found on the root of volumes, mostly old disk images. It is either 40 or 48 bytes long.
+///
+/// It starts with the same window information as "fwi0". The rest is kept as it was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FinderInfo {
+    pub window: WindowInfo,
+    /// The 24 bytes after the window information. Their meaning is unknown.
+    pub unknown: [u8; 24],
+    /// The 8 bytes only the 48-byte form has. Their meaning is unknown.
+    pub extra: Option<[u8; 8]>,
+}
+
+/// A point in time, stored as a "dutc" value:
+/// the number of 1/65536ths of a second since 1904-01-01 00:00:00 UTC, the Mac epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(i64);
+
+impl Timestamp {
+    /// How many ticks make a second.
+    pub const TICKS_PER_SECOND: i64 = 65536;
+    /// Seconds from the Mac epoch to the Unix epoch.
+    const UNIX_EPOCH_OFFSET: i64 = 2082844800;
+
+    pub fn from_ticks(ticks: i64) -> Timestamp {
+        Timestamp(ticks)
+    }
+
+    /// The raw value, in 1/65536ths of a second since the Mac epoch.
+    pub fn ticks(self) -> i64 {
+        self.0
+    }
+
+    /// Whole seconds since the Unix epoch, rounded down.
+    pub fn unix_seconds(self) -> i64 {
+        self.0.div_euclid(Timestamp::TICKS_PER_SECOND) - Timestamp::UNIX_EPOCH_OFFSET
+    }
+
+    /// The fraction of a second after `unix_seconds`, in 1/65536ths of a second.
+    pub fn fraction(self) -> u16 {
+        self.0.rem_euclid(Timestamp::TICKS_PER_SECOND) as u16
+    }
+
+    /// Converts to a `chrono::DateTime`, rounding the fraction to the nearest nanosecond.
+    /// Returns `None` if the timestamp is out of `chrono`'s range.
+    pub fn to_date_time(self) -> Option<chrono::DateTime<chrono::Utc>> {
+        let nanos = (i64::from(self.fraction()) * 1_000_000_000 + Timestamp::TICKS_PER_SECOND / 2) / Timestamp::TICKS_PER_SECOND;
+        chrono::Utc.timestamp_opt(self.unix_seconds(), nanos as u32).single()
+    }
+
+    /// Converts from a `chrono::DateTime`, rounding to the nearest tick.
+    /// Converting a timestamp to a `chrono::DateTime` and back gives the same timestamp.
+    pub fn from_date_time(date_time: &chrono::DateTime<chrono::Utc>) -> Timestamp {
+        let seconds = date_time.timestamp() + Timestamp::UNIX_EPOCH_OFFSET;
+        let nanos = i64::from(date_time.timestamp_subsec_nanos());
+        let fraction = (nanos * Timestamp::TICKS_PER_SECOND + 500_000_000) / 1_000_000_000;
+        Timestamp(seconds * Timestamp::TICKS_PER_SECOND + fraction)
+    }
+}
+
 /// A Block is a u8-slice, with methods for reading from it in _Big-Endian format_.
 struct Block<'a>(&'a [u8]);
 
@@ -119,7 +451,7 @@ impl<'a> Block<'a> {
     fn read_i64(&mut self) -> Result<i64, Error<'a>> {
         self.len_check(8)?;
         let ret = Ok(BigEndian::read_i64(self.0));
-        self.0 = &self.0[4..];
+        self.0 = &self.0[8..];
         ret
     }
 
@@ -141,18 +473,24 @@ impl<'a> Block<'a> {
         Ok(left)
     }
 
-    /// Reads a 4-byte `length` and then reads (`length*2`)-bytes to create a `String`.
-    fn read_utf16(&mut self) -> Result<String, Error<'a>> {
-        // TODO: Small possible optimization opportinuty,
-        // only has to allocate for String on big-endian machines.
-        // as you can just slice::from_raw_parts the &[u8] -> &[u16] and itll just work.
-        // Would need to dupe this function with #[cfg(target_endian=little/big)]
+    /// Reads a 4-byte `length` and then `length` UTF-16 units.
+    fn read_utf16_units(&mut self) -> Result<Vec<u16>, Error<'a>> {
         let file_name_length = self.read_u32()?;
         let mut u16_buf: Vec<u16> = Vec::with_capacity(file_name_length as usize * 2);
 
         for _ in 0..file_name_length {
             u16_buf.push(self.read_u16()?);
         }
+        Ok(u16_buf)
+    }
+
+    /// Reads a 4-byte `length` and then reads (`length*2`)-bytes to create a `String`.
+    fn read_utf16(&mut self) -> Result<String, Error<'a>> {
+        // TODO: Small possible optimization opportinuty,
+        // only has to allocate for String on big-endian machines.
+        // as you can just slice::from_raw_parts the &[u8] -> &[u16] and itll just work.
+        // Would need to dupe this function with #[cfg(target_endian=little/big)]
+        let u16_buf = self.read_utf16_units()?;
 
         match String::from_utf16(&u16_buf) {
             Err(_) => Err(Error::InvalidString),
@@ -163,31 +501,103 @@ impl<'a> Block<'a> {
     // Reads a 4-byte length, then length-bytes of self.
     fn read_blob(&mut self) -> Result<&'a [u8], Error<'a>> {
         let length = self.read_u32()?;
-        Ok(self.read_buf(length as usize)?)
+        self.read_buf(length as usize)
     }
 
-    fn read_record(&mut self, records: &mut HashMap<String, HashMap<&'a str, RecordValue<'a>>>) -> Result<(), Error<'a>> {
+    /// Reads a record, returning its filename, record type and value.
+    fn read_record(&mut self) -> Result<(String, &'a str, RecordValue<'a>), Error<'a>> {
         let file_name = self.read_utf16()?;
-        let mut metadata = records.entry(file_name).or_insert(HashMap::new());
-        self.read_record_info(&mut metadata)?;
-        Ok(())
+        let (structure_type, value) = self.read_record_info()?;
+        Ok((file_name, structure_type, value))
+    }
+
+    /// Reads a record like `read_record`, also returning the bytes of its record type, data type and value.
+    fn read_record_with_source(&mut self) -> Result<(String, &'a str, RecordValue<'a>, &'a [u8]), Error<'a>> {
+        let file_name = self.read_utf16()?;
+        let start = self.0;
+        let (structure_type, value) = self.read_record_info()?;
+        Ok((file_name, structure_type, value, &start[..start.len() - self.0.len()]))
+    }
+
+    fn read_date_time(&mut self) -> Result<Timestamp, Error<'a>> {
+        Ok(Timestamp::from_ticks(self.read_i64()?))
+    }
+
+    /// Reads a data type, and then a value of that type.
+    fn read_generic_value(&mut self) -> Result<GenericValue<'a>, Error<'a>> {
+        match self.read_buf(4)? {
+            b"long" => Ok(GenericValue::Long(self.read_i32()?)),
+            b"shor" => {
+                self.skip(2)?;
+                Ok(GenericValue::Short(self.read_i16()?))
+            },
+            b"bool" => Ok(GenericValue::Bool(self.read_bool()?)),
+            b"blob" => Ok(GenericValue::Blob(self.read_blob()?)),
+            b"type" => Ok(GenericValue::Type(self.read_buf(4)?)),
+            b"ustr" => Ok(GenericValue::UStr(self.read_utf16()?)),
+            b"comp" => Ok(GenericValue::Comp(self.read_i64()?)),
+            b"dutc" => Ok(GenericValue::DateTime(self.read_date_time()?)),
+            other => Err(Error::UnkonwnStructureType(other)),
+        }
     }
 
-    fn read_date_time(&mut self) -> Result<chrono::DateTime<chrono::Utc>, Error<'a>> {
-        // number that when added to a Mac-epoch integer, converts it to a Unix-epoch integer.
-        // TODO: MAKE SURE THIS WORKS?
-        const CONVERTER: i64 = 2082844800;
-        let raw = self.read_i64()?;
-        println!("DateTime raw: {}", raw);
-        Ok(chrono::DateTime::from_utc(chrono::NaiveDateTime::from_timestamp(raw + CONVERTER, 0), chrono::Utc))
+    /// Reads a blob holding a binary property list.
+    /// Kept as a blob if it doesn't parse, like "pict", so one bad plist doesn't hide the rest of the file.
+    fn read_plist_value(&mut self) -> Result<RecordValue<'a>, Error<'a>> {
+        let blob = self.read_blob()?;
+        match PlistRecord::parse(blob) {
+            Ok(record) => Ok(RecordValue::Plist(record)),
+            Err(_) => Ok(RecordValue::Slice(blob)),
+        }
+    }
+
+    /// Skips a data type and a value of that type, without decoding the value.
+    fn skip_value(&mut self) -> Result<(), Error<'a>> {
+        let amount = match self.read_buf(4)? {
+            b"bool" => 1,
+            b"long" | b"shor" | b"type" => 4,
+            b"comp" | b"dutc" => 8,
+            b"blob" => self.read_u32()? as usize,
+            b"ustr" => self.read_u32()? as usize * 2,
+            other => return Err(Error::UnkonwnStructureType(other)),
+        };
+        self.skip(amount)
+    }
+
+    /// The 16 bytes of "fwi0", also found at the start of "info".
+    fn read_window_info(&mut self) -> Result<WindowInfo, Error<'a>> {
+        let top = self.read_i16()?;
+        let left = self.read_i16()?;
+        let bottom = self.read_i16()?;
+        let right = self.read_i16()?;
+        let mut view_style = [0; 4];
+        view_style.copy_from_slice(self.read_buf(4)?);
+        let mut unknown = [0; 4];
+        unknown.copy_from_slice(self.read_buf(4)?);
+        Ok(WindowInfo {top, left, bottom, right, view_style, unknown})
+    }
+
+    fn read_record_info(&mut self) -> Result<(&'a str, RecordValue<'a>), Error<'a>> {
+        let type_str = self.read_record_type()?;
+        let record_value = self.read_record_value(type_str)?;
+        Ok((type_str, record_value))
     }
 
-    // TODO: better strongly type the RecordValues. "bwsp" is actually a plist. Many blobs are meaningful.
-    fn read_record_info(&mut self, records: &mut HashMap<&'a str, RecordValue<'a>>) -> Result<(), Error<'a>> {
-        let structure_type: &'a [u8] = self.read_buf(4)?;
-        let record_value: RecordValue = match structure_type {
+    /// Reads the 4-byte record type (code) of a record.
+    fn read_record_type(&mut self) -> Result<&'a str, Error<'a>> {
+        match std::str::from_utf8(self.read_buf(4)?) {
+            Ok(s) => Ok(s),
+            Err(_) => Err(Error::InvalidString),
+        }
+    }
+
+    /// Reads the value of a `type_str` record, starting at its data type.
+    // TODO: better strongly type the RecordValues. Many blobs are meaningful.
+    fn read_record_value(&mut self, type_str: &'a str) -> Result<RecordValue<'a>, Error<'a>> {
+        match type_str.as_bytes() {
             b"BKGD" => {
                 self.read_exact(b"blob", "\"BKGD\" only takes blobs describing the background.")?;
+                self.read_exact(&[0,0,0,12], "\"BKGD\" only takes a 12-byte blob.")?;
                 match self.read_buf(4)? {
                     b"DefB" => {
                         self.skip(8)?;
@@ -203,7 +613,7 @@ impl<'a> Block<'a> {
                     b"PctB" => {
                         let picture_property_blob_length = self.read_u32()?;
                         self.skip(4)?;
-                        Ok(RecordValue::U32(picture_property_blob_length))
+                        Ok(RecordValue::Background(BackgroundType::Picture(picture_property_blob_length, None)))
                     },
                     other => Err(Error::UnkonwnStructureType(other))
                 }
@@ -214,9 +624,13 @@ impl<'a> Block<'a> {
                 Ok(RecordValue::Bool(self.read_bool()?))
             },
             b"Iloc" => {
-                self.read_exact(b"blob", "\"Iloc\" only takes bool")?;
+                self.read_exact(b"blob", "\"Iloc\" only takes blob")?;
                 self.read_exact(&[0,0,0,16], "\"Iloc\" only takes a 16-byte blob.")?;
-                Ok(RecordValue::Slice(self.read_buf(16)?))
+                let x = self.read_u32()?;
+                let y = self.read_u32()?;
+                let mut unknown = [0; 8];
+                unknown.copy_from_slice(self.read_buf(8)?);
+                Ok(RecordValue::IconLocation(IconLocation {x, y, unknown}))
             },
             b"LSVO" => {
                 self.read_exact(b"bool", "\"LSVO\" only takes bool")?;
@@ -224,7 +638,7 @@ impl<'a> Block<'a> {
             },
             b"bwsp" => {
                 self.read_exact(b"blob", "\"bwsp\" only takes blob")?;
-                Ok(RecordValue::Slice(self.read_blob()?))
+                self.read_plist_value()
             },
             b"cmmt" => {
                 self.read_exact(b"ustr", "\"cmmt\" only takes ustr")?;
@@ -233,7 +647,14 @@ impl<'a> Block<'a> {
             b"dilc" => {
                 self.read_exact(b"blob", "\"dilc\" only takes blob")?;
                 self.read_exact(&[0,0,0,32], "\"dilc\" only takes a 32-byte blob.")?;
-                Ok(RecordValue::Slice(self.read_buf(32)?))
+                let flags = self.read_u32()?;
+                let mut unknown = [0; 4];
+                unknown.copy_from_slice(self.read_buf(4)?);
+                let x = self.read_i32()?;
+                let y = self.read_i32()?;
+                let mut trailing = [0; 16];
+                trailing.copy_from_slice(self.read_buf(16)?);
+                Ok(RecordValue::DesktopIconLocation(DesktopIconLocation {flags, unknown, x, y, trailing}))
             },
             b"dscl" => {
                 self.read_exact(b"bool", "\"dscl\" only takes bool")?;
@@ -246,7 +667,7 @@ impl<'a> Block<'a> {
             b"fwi0" => {
                 self.read_exact(b"blob", "\"fwi0\" only takes blob")?;
                 self.read_exact(&[0,0,0,16], "\"fwi0\" only takes 16-byte blob")?;
-                Ok(RecordValue::Slice(self.read_buf(16)?))
+                Ok(RecordValue::WindowInfo(self.read_window_info()?))
             },
             b"fwsw" => {
                 self.read_exact(b"long", "\"fwsw\" only takes long")?;
@@ -274,16 +695,32 @@ impl<'a> Block<'a> {
             b"icvo" => {
                 self.read_exact(b"blob", "\"icvo\" only takes blob")?;
                 let blob = self.read_blob()?;
+                let mut arrangement = [0; 4];
 
-                if blob.len() == 18 || blob.len() == 26 {
-                    Ok(RecordValue::Slice(blob))
-                } else {
-                    Err(Error::BadData("\"icvo\" only takes 18 or 26 byte blob."))
+                match (blob.len(), blob.get(..4)) {
+                    (18, Some(b"icvo")) => {
+                        let mut unknown = [0; 8];
+                        unknown.copy_from_slice(&blob[4..12]);
+                        arrangement.copy_from_slice(&blob[14..18]);
+                        let icon_size = BigEndian::read_u16(&blob[12..14]);
+                        Ok(RecordValue::LegacyIconView(LegacyIconViewOptions::Icvo {unknown, icon_size, arrangement}))
+                    },
+                    (26, Some(b"icv4")) => {
+                        let icon_size = BigEndian::read_u16(&blob[4..6]);
+                        arrangement.copy_from_slice(&blob[6..10]);
+                        let mut label_position = [0; 4];
+                        label_position.copy_from_slice(&blob[10..14]);
+                        let mut unknown = [0; 12];
+                        unknown.copy_from_slice(&blob[14..26]);
+                        Ok(RecordValue::LegacyIconView(LegacyIconViewOptions::Icv4 {icon_size, arrangement, label_position, unknown}))
+                    },
+                    (18, _) | (26, _) => Err(Error::BadData("\"icvo\" blob doesn't start with \"icvo\" or \"icv4\".")),
+                    _ => Err(Error::BadData("\"icvo\" only takes 18 or 26 byte blob.")),
                 }
             },
             b"icvp" => {
                 self.read_exact(b"blob", "\"icvp\" only takes blob")?;
-                Ok(RecordValue::Slice(self.read_blob()?))
+                self.read_plist_value()
             },
             b"icvt" => {
                 self.read_exact(b"shor", "\"icvt\" only takes shor")?;
@@ -292,12 +729,22 @@ impl<'a> Block<'a> {
             },
             b"info" => {
                 self.read_exact(b"blob", "\"info\" only takes blob")?;
-                let blob = self.read_blob()?;
-                if blob.len() == 40 || blob.len() == 48 {
-                    Ok(RecordValue::Slice(blob))
+                let extra = match self.read_u32()? {
+                    40 => false,
+                    48 => true,
+                    _ => return Err(Error::BadData("\"info\" only takes 40 or 48 byte blob.")),
+                };
+                let window = self.read_window_info()?;
+                let mut unknown = [0; 24];
+                unknown.copy_from_slice(self.read_buf(24)?);
+                let extra = if extra {
+                    let mut extra = [0; 8];
+                    extra.copy_from_slice(self.read_buf(8)?);
+                    Some(extra)
                 } else {
-                    Err(Error::BadData("\"info\" only takes 40 or 48 byte blob."))
-                }
+                    None
+                };
+                Ok(RecordValue::FinderInfo(FinderInfo {window, unknown, extra}))
             },
             b"logS" | b"lg1S" => {
                 self.read_exact(b"comp", "\"logS\"/\"lg1S\" only takes comp")?;
@@ -311,6 +758,8 @@ impl<'a> Block<'a> {
             b"lsvo" => {
                 self.read_exact(b"blob", "\"lsvo\" only takes blob")?;
                 self.read_exact(&[0,0,0,76], "\"lsvo\" only takes 76-byte blob")?;
+                // Unlike "icvo" and "fwi0", no description of these 76 bytes has been published,
+                // and there is no sample to work them out from, so the blob is kept as is.
                 Ok(RecordValue::Slice(self.read_buf(76)?))
             },
             b"lsvt" => {
@@ -320,11 +769,11 @@ impl<'a> Block<'a> {
             },
             b"lsvp" => {
                 self.read_exact(b"blob", "\"lsvp\" only takes blob")?;
-                Ok(RecordValue::Slice(self.read_blob()?))
+                self.read_plist_value()
             },
             b"lsvP" => {
                 self.read_exact(b"blob", "\"lsvP\" only takes blob")?;
-                Ok(RecordValue::Slice(self.read_blob()?))
+                self.read_plist_value()
             },
             b"modD" | b"moDD" => {
                 self.read_exact(b"dutc", "\"modD\"/\"moDD\" only takes dutc")?;
@@ -334,12 +783,24 @@ impl<'a> Block<'a> {
                 self.read_exact(b"comp", "\"phyS\"/\"ph1S\" only takes comp")?;
                 Ok(RecordValue::I64(self.read_i64()?))
             },
+            // Bookmarks to the background picture, which newer versions of Finder write next to "pict".
+            b"pBBk" | b"pBB0" => {
+                self.read_exact(b"blob", "\"pBBk\" and \"pBB0\" only take blob")?;
+                let blob = self.read_blob()?;
+                // Kept as a blob if it is not a bookmark, like "pict".
+                match BookmarkRecord::parse(blob) {
+                    Ok(bookmark) => Ok(RecordValue::Bookmark(bookmark)),
+                    Err(_) => Ok(RecordValue::Slice(blob)),
+                }
+            },
             b"pict" => {
-                // I really hope that `pic` is a regular blob,
-                // but the docs are unclear if we need to get the length from the 'BKGD' key.
                 self.read_exact(b"blob", "\"pict\" only takes blob")?;
-                //  TODO: maybe do a verify, get the BKGD key and check they are the same?
-                Ok(RecordValue::Slice(self.read_blob()?))
+                let blob = self.read_blob()?;
+                // Kept as a blob if it is not an Alias record, so an unexpected picture reference doesn't hide the rest.
+                match AliasRecord::parse(blob) {
+                    Ok(alias) => Ok(RecordValue::Alias(alias)),
+                    Err(_) => Ok(RecordValue::Slice(blob)),
+                }
             },
             b"vSrn" => {
                 self.read_exact(b"long", "\"vSrn\" only takes long")?;
@@ -364,17 +825,9 @@ impl<'a> Block<'a> {
                 self.read_exact(b"ustr", "\"ptbN\" only takes ustr")?;
                 Ok(RecordValue::String(self.read_utf16()?))
             },
-            other => Err(Error::UnkonwnStructureType(other)),
-        }?;
-        // should be impossible to hit error case,
-        // but is getting annoyed by people saying I shouldnt use unsafe code worth it? :p joking of course.
-        let type_str = match std::str::from_utf8(structure_type) {
-            Ok(s) => s,
-            Err(_) => { return Err(Error::InvalidString); }
-        };
-        // TODO: maybe check if dupe, and Err if so?
-        records.insert(type_str, record_value);
-        Ok(())
+            // Finder keeps adding record types, so decode what we can instead of giving up.
+            _ => Ok(RecordValue::Unknown(type_str, self.read_generic_value()?)),
+        }
     }
 }
 
@@ -382,6 +835,14 @@ pub struct Allocator<'a> {
     /// The whole data to be partitioned into blocks by the allocator.
     data: &'a [u8],
 
+    /// The 16 bytes following the offset check in the prelude. Their meaning is unknown.
+    pub prelude_unknown: &'a [u8],
+    /// Offset of the info block, as given by the prelude.
+    pub info_block_offset: u32,
+    /// Size of the info block, as given by the prelude.
+    pub info_block_size: u32,
+    /// The 4 bytes following the offset count. Documented as unknown, always observed as 0.
+    pub offsets_unknown: u32,
     /// The offsets to each block(?) (TODO write this.)
     pub offsets: Vec<u32>,
     /// It is a 'table of contents', but it seems that there is only ever 1 entry, "DSDB".
@@ -393,24 +854,31 @@ pub struct Allocator<'a> {
 impl<'a> Allocator<'a> {
     /// Create a new alloctor, initalizing all important data needed for traversal.
     pub fn new(data: &'a [u8]) -> Result<Allocator<'a>, Error<'a>> {
-        if &data[0..4] != &[0,0,0,1] {
+        if data[0..4] != [0,0,0,1] {
             // creating a block offsets by 4 bytes, so check the first 4 here.
             return Err(Error::BadData("First 4 bytes must be `1`."));
         }
         let mut prelude_block = Block::new(data, 0, 32)?;
 
         let (info_block_offset, info_block_size) = Allocator::read_prelude(&mut prelude_block)?;
+        let prelude_unknown = prelude_block.read_buf(16)?;
         let mut info_block = Block::new(data, info_block_offset as usize, info_block_size as usize)?;
 
-        let offsets = Allocator::read_offsets(&mut info_block)?;
+        let (offsets, offsets_unknown) = Allocator::read_offsets(&mut info_block)?;
         let dsdb_location = Allocator::read_dsdb_location(&mut info_block)?;
         let free_list = Allocator::read_free_list(&mut info_block)?;
 
-        Ok(Allocator {data, offsets, dsdb_location, free_list}) // allocator should be fully allocated here.
+        // allocator should be fully allocated here.
+        Ok(Allocator {data, prelude_unknown, info_block_offset, info_block_size, offsets_unknown, offsets, dsdb_location, free_list})
+    }
+
+    /// The whole data the allocator was created from.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
     }
 
     fn get_block(&self, block_id: u32) -> Result<Block<'a>, Error<'a>> {
-        if self.offsets.len() < block_id as usize {
+        if self.offsets.len() <= block_id as usize {
             return Err(Error::BlockDoesntExist);
         }
         let address = self.offsets[block_id as usize];
@@ -433,20 +901,20 @@ impl<'a> Allocator<'a> {
         Ok((offset, size))
     }
 
-    fn read_offsets(info_block: &mut Block<'a>) -> Result<Vec<u32>, Error<'a>> {
+    fn read_offsets(info_block: &mut Block<'a>) -> Result<(Vec<u32>, u32), Error<'a>> {
         let num_offsets = info_block.read_u32()?;
         let mut offsets = Vec::with_capacity(num_offsets as usize);
         // Documented as unknown bytes, always observed as 0.
-        info_block.read_exact(&[0,0,0,0], "Thought these should always be 0???")?;
+        let unknown = info_block.read_u32()?;
         for _i in 0..num_offsets {
             offsets.push(info_block.read_u32()?);
         }
 
-        // TODO: verify this math...
-        // Also document this (Offsets section of https://0day.work post.)
-        let bytes_to_skip = (256 - (num_offsets % 256)) * 4;
+        // The offsets are padded with zeroes to a multiple of 256 entries.
+        // (Offsets section of the https://0day.work post.)
+        let bytes_to_skip = ((256 - (num_offsets % 256)) % 256) * 4;
         info_block.skip(bytes_to_skip as usize)?;
-        Ok(offsets)
+        Ok((offsets, unknown))
     }
 
     fn read_dsdb_location(info_block: &mut Block<'a>) -> Result<u32, Error<'a>> {
@@ -454,7 +922,7 @@ impl<'a> Allocator<'a> {
         info_block.read_exact(&[0,0,0,1], "I Thought there should only be 1 TOC entry...")?;
         info_block.read_exact(&[4], "Looks like \"DSDB\" is not the only key...")?;
         info_block.read_exact(b"DSDB", "I thought only key was \"DSDB\"...")?;
-        Ok(info_block.read_u32()?) // value!
+        info_block.read_u32() // value!
     }
 
     fn read_free_list(info_block: &mut Block<'a>) -> Result<Vec<Vec<u32>>, Error<'a>>  {
@@ -470,7 +938,8 @@ impl<'a> Allocator<'a> {
         Ok(free_list)
     }
 
-    pub fn traverse(&self) -> Result<Directory<'a>, Error<'a>> {
+    /// Reads the DSDB block: the root node, number of internal levels, number of records and number of nodes of the B-Tree.
+    fn read_dsdb(&self) -> Result<(u32, u32, u32, u32), Error<'a>> {
         let mut root_block = self.get_block(self.dsdb_location)?;
         let root_node = root_block.read_u32()?;
         let num_internals = root_block.read_u32()?;
@@ -478,29 +947,362 @@ impl<'a> Allocator<'a> {
         let num_nodes = root_block.read_u32()?;
 
         root_block.read_exact(&[0,0, 0x10, 0], "Expected 0x1000, found not that.")?;
-        let mut contents = HashMap::new();
-        self.traverse_tree(root_node, &mut contents)?;
-        Ok(Directory {num_internals, num_records, num_nodes, contents})
+        Ok((root_node, num_internals, num_records, num_nodes))
+    }
+
+    /// Reads every record of the B-Tree, keeping records stored more than once as `Duplicates::Keep` does.
+    pub fn traverse(&self) -> Result<Directory<'a>, Error<'a>> {
+        self.traverse_with(Duplicates::Keep)
     }
 
-    fn traverse_tree(&self, block_id: u32, contents: &mut HashMap<String, HashMap<&'a str, RecordValue<'a>>>) -> Result<(), Error<'a>> {
+    /// Reads every record of the B-Tree, handling records stored more than once as `duplicates` says.
+    pub fn traverse_with(&self, duplicates: Duplicates) -> Result<Directory<'a>, Error<'a>> {
+        let (root_node, num_internals, num_records, num_nodes) = self.read_dsdb()?;
+        let mut records = TreeRecords {duplicates, contents: BTreeMap::new(), locations: BTreeMap::new(), repeated: BTreeMap::new()};
+        let mut nodes = Vec::new();
+        self.traverse_tree(root_node, num_internals, &mut records, &mut nodes)?;
+        let TreeRecords {mut contents, repeated, ..} = records;
+        resolve_background_pictures(&mut contents);
+        let duplicates = repeated.into_iter()
+            .map(|((file_name, code), occurrences)| DuplicateRecord {file_name, code, occurrences})
+            .collect();
+        Ok(Directory {root_node, num_internals, num_records, num_nodes, contents, nodes, duplicates})
+    }
+
+    /// Walks the subtree rooted at `block_id`, which may have at most `levels_left` internal levels.
+    ///
+    /// A node starts with the block ID of its rightmost child (0 for leaves), and its record count.
+    /// Internal nodes then hold (child, record) pairs, where every record in the child sorts before the record,
+    /// and every record in the rightmost child sorts after all of them.
+    fn traverse_tree(&self, block_id: u32, levels_left: u32, records: &mut TreeRecords<'a>,
+                     nodes: &mut Vec<NodeLayout<'a>>) -> Result<(), Error<'a>> {
         let mut current_block = self.get_block(block_id)?;
 
-        let pair_count = current_block.read_u32()?;
-        if pair_count == 0 {
+        let rightmost_child = current_block.read_u32()?;
+        let count = current_block.read_u32()?;
+        let mut node = NodeLayout {block_id, rightmost_child, records: Vec::with_capacity(count as usize)};
+        if rightmost_child == 0 {
             // We are at a leaf! Congratulations!
-            let count = current_block.read_u32()?;
-            for _ in 0..count {
-                current_block.read_record(contents)?;
+            for index in 0..count as usize {
+                let (file_name, structure_type, value, source) = current_block.read_record_with_source()?;
+                records.insert(file_name.clone(), structure_type, value, RecordLocation {block_id, index})?;
+                node.records.push((0, file_name, structure_type, source));
             }
         } else {
             // Internal node of the B-Tree!
-            for _ in 0..pair_count {
+            // Checking the depth also keeps a malformed tree from looping forever.
+            if levels_left == 0 {
+                return Err(Error::BadData("B-Tree is deeper than the DSDB block says."));
+            }
+            for index in 0..count as usize {
                 let child = current_block.read_u32()?;
-                self.traverse_tree(child, contents)?;
-                current_block.read_record(contents)?;
+                self.traverse_tree(child, levels_left - 1, records, nodes)?;
+                let (file_name, structure_type, value, source) = current_block.read_record_with_source()?;
+                records.insert(file_name.clone(), structure_type, value, RecordLocation {block_id, index})?;
+                node.records.push((child, file_name, structure_type, source));
+            }
+            self.traverse_tree(rightmost_child, levels_left - 1, records, nodes)?;
+        }
+        nodes.push(node);
+        Ok(())
+    }
+
+    /// Iterates over the records of the B-Tree as (filename, code, value), in the order they are stored in,
+    /// reading each node only when the iteration reaches it. Only the nodes from the root to the current one are kept.
+    /// The iteration stops after the first error, which is returned as the last item.
+    ///
+    /// Records stored more than once are all returned, and picture backgrounds are returned without their alias,
+    /// which is in the "pict" record of the same file.
+    pub fn records<'b>(&'b self) -> Records<'a, 'b> {
+        Records {allocator: self, stack: Vec::new(), started: false, done: false}
+    }
+
+    /// Walks the B-Tree depth first, in the order records are stored in, telling `visitor` what it finds.
+    /// Values the visitor skips in `Visitor::record_key` are passed over using their data type alone, without decoding them.
+    pub fn visit<V: Visitor<'a>>(&self, visitor: &mut V) -> Result<(), Error<'a>> {
+        let (root_node, num_internals, _, _) = self.read_dsdb()?;
+        self.visit_node(root_node, num_internals, visitor)
+    }
+
+    fn visit_node<V: Visitor<'a>>(&self, block_id: u32, levels_left: u32, visitor: &mut V) -> Result<(), Error<'a>> {
+        let (mut block, rightmost_child, count) = self.read_node_header(block_id, levels_left)?;
+        visitor.enter_node(block_id, rightmost_child == 0, count);
+        for _ in 0..count {
+            if rightmost_child != 0 {
+                let child = block.read_u32()?;
+                self.visit_node(child, levels_left - 1, visitor)?;
+            }
+            let file_name = block.read_utf16()?;
+            let code = block.read_record_type()?;
+            match visitor.record_key(&file_name, code) {
+                RecordAction::Decode => {
+                    let value = block.read_record_value(code)?;
+                    visitor.record_value(&file_name, code, value);
+                },
+                RecordAction::Skip => block.skip_value()?,
+            }
+        }
+        if rightmost_child != 0 {
+            self.visit_node(rightmost_child, levels_left - 1, visitor)?;
+        }
+        visitor.exit_node(block_id);
+        Ok(())
+    }
+
+    /// Finds the `code` record of `file_name` by descending the B-Tree from its root,
+    /// only reading the nodes on the way and only decoding the value of that record.
+    /// Filenames are matched as the B-Tree compares them (see `hfs::compare_file_names`), so case does not matter.
+    ///
+    /// A picture background is returned without its alias, which is in the "pict" record: see `find_records`.
+    pub fn find_record(&self, file_name: &str, code: &str) -> Result<Option<RecordValue<'a>>, Error<'a>> {
+        let (root_node, num_internals, _, _) = self.read_dsdb()?;
+        let name: Vec<u16> = file_name.encode_utf16().collect();
+        self.find_in_tree(root_node, num_internals, &name, code)
+    }
+
+    /// Finds every record of `file_name` as (code, value) by descending the B-Tree from its root,
+    /// only reading the nodes that can hold them and only decoding their values.
+    /// Records stored more than once are all returned, in the order they are stored in.
+    pub fn find_records(&self, file_name: &str) -> Result<Vec<(&'a str, RecordValue<'a>)>, Error<'a>> {
+        let (root_node, num_internals, _, _) = self.read_dsdb()?;
+        let name: Vec<u16> = file_name.encode_utf16().collect();
+        let mut found = Vec::new();
+        self.find_all_in_tree(root_node, num_internals, &name, &mut found)?;
+
+        let alias = found.iter().find_map(|&(code, ref value)| match *value {
+            RecordValue::Alias(ref record) if code == "pict" => Some(record.value().clone()),
+            _ => None,
+        });
+        if let Some(alias) = alias {
+            for &mut (code, ref mut value) in found.iter_mut() {
+                if let (RecordValue::Background(BackgroundType::Picture(_, ref mut picture)), "BKGD") = (value, code) {
+                    *picture = Some(alias.clone());
+                }
+            }
+        }
+        Ok(found)
+    }
+
+    /// Reads the rightmost child and record count of the node in `block_id`, checking it may be internal.
+    fn read_node_header(&self, block_id: u32, levels_left: u32) -> Result<(Block<'a>, u32, u32), Error<'a>> {
+        let mut block = self.get_block(block_id)?;
+        let rightmost_child = block.read_u32()?;
+        let count = block.read_u32()?;
+        if rightmost_child != 0 && levels_left == 0 {
+            return Err(Error::BadData("B-Tree is deeper than the DSDB block says."));
+        }
+        Ok((block, rightmost_child, count))
+    }
+
+    fn find_in_tree(&self, block_id: u32, levels_left: u32, name: &[u16], code: &str) -> Result<Option<RecordValue<'a>>, Error<'a>> {
+        let (mut block, rightmost_child, count) = self.read_node_header(block_id, levels_left)?;
+        for _ in 0..count {
+            let child = if rightmost_child != 0 { block.read_u32()? } else { 0 };
+            let record_name = block.read_utf16_units()?;
+            let record_code = block.read_record_type()?;
+            match fast_unicode_compare(name, &record_name).then_with(|| code.cmp(record_code)) {
+                // Every record after this one sorts after the key too, so only the child can hold it.
+                Ordering::Less if child != 0 => return self.find_in_tree(child, levels_left - 1, name, code),
+                Ordering::Less => return Ok(None),
+                Ordering::Equal => return block.read_record_value(record_code).map(Some),
+                Ordering::Greater => block.skip_value()?,
             }
         }
+        if rightmost_child != 0 {
+            self.find_in_tree(rightmost_child, levels_left - 1, name, code)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn find_all_in_tree(&self, block_id: u32, levels_left: u32, name: &[u16],
+                        found: &mut Vec<(&'a str, RecordValue<'a>)>) -> Result<(), Error<'a>> {
+        let (mut block, rightmost_child, count) = self.read_node_header(block_id, levels_left)?;
+        for _ in 0..count {
+            let child = if rightmost_child != 0 { block.read_u32()? } else { 0 };
+            let record_name = block.read_utf16_units()?;
+            match fast_unicode_compare(name, &record_name) {
+                Ordering::Less => {
+                    if child != 0 {
+                        self.find_all_in_tree(child, levels_left - 1, name, found)?;
+                    }
+                    return Ok(());
+                },
+                Ordering::Equal => {
+                    // Records of the same file can also be at the end of the child.
+                    if child != 0 {
+                        self.find_all_in_tree(child, levels_left - 1, name, found)?;
+                    }
+                    let record_code = block.read_record_type()?;
+                    found.push((record_code, block.read_record_value(record_code)?));
+                },
+                Ordering::Greater => {
+                    block.skip(4)?;
+                    block.skip_value()?;
+                },
+            }
+        }
+        if rightmost_child != 0 {
+            self.find_all_in_tree(rightmost_child, levels_left - 1, name, found)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether `Allocator::visit` should decode the value of a record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordAction {
+    /// Decode the value, and pass it to `Visitor::record_value`.
+    Decode,
+    /// Pass over the value without decoding it.
+    Skip,
+}
+
+/// Receives the B-Tree as `Allocator::visit` walks it.
+/// Every hook does nothing by default, and every value is decoded.
+pub trait Visitor<'a> {
+    /// A node in `block_id` is entered, before any of its `records` and children.
+    fn enter_node(&mut self, _block_id: u32, _leaf: bool, _records: u32) {}
+
+    /// The node in `block_id` is left, after all of its records and children.
+    fn exit_node(&mut self, _block_id: u32) {}
+
+    /// The key of the next record, returning whether its value should be decoded.
+    fn record_key(&mut self, _file_name: &str, _code: &'a str) -> RecordAction {
+        RecordAction::Decode
+    }
+
+    /// The value of the record whose key was just visited, if it was decoded.
+    /// Picture backgrounds come without their alias, which is in the "pict" record of the same file.
+    fn record_value(&mut self, _file_name: &str, _code: &'a str, _value: RecordValue<'a>) {}
+}
+
+/// An iterator over the records of a B-Tree, created by `Allocator::records`.
+pub struct Records<'a, 'b> {
+    allocator: &'b Allocator<'a>,
+    /// The nodes from the root to the current one.
+    stack: Vec<NodeCursor<'a>>,
+    started: bool,
+    done: bool,
+}
+
+/// A B-Tree node being read by `Records`.
+struct NodeCursor<'a> {
+    /// The rest of the node, from the next record or child pointer on.
+    block: Block<'a>,
+    levels_left: u32,
+    rightmost_child: u32,
+    /// The number of records left to read.
+    remaining: u32,
+    /// Whether a child comes before the next record (or is the rightmost child, once all records are read).
+    child_due: bool,
+}
+
+impl<'a, 'b> Records<'a, 'b> {
+    fn push_node(&mut self, block_id: u32, levels_left: u32) -> Result<(), Error<'a>> {
+        let (block, rightmost_child, remaining) = self.allocator.read_node_header(block_id, levels_left)?;
+        self.stack.push(NodeCursor {block, levels_left, rightmost_child, remaining, child_due: rightmost_child != 0});
         Ok(())
     }
+
+    /// Moves on to the next record, descending into the children that come before it.
+    fn advance(&mut self) -> Result<Option<(String, &'a str, RecordValue<'a>)>, Error<'a>> {
+        if !self.started {
+            self.started = true;
+            let (root_node, num_internals, _, _) = self.allocator.read_dsdb()?;
+            self.push_node(root_node, num_internals)?;
+        }
+        loop {
+            let node = match self.stack.last_mut() {
+                Some(node) => node,
+                None => return Ok(None),
+            };
+            if node.child_due {
+                node.child_due = false;
+                let levels_left = node.levels_left - 1;
+                let child = if node.remaining > 0 {
+                    node.block.read_u32()?
+                } else {
+                    // Nothing is left of the node after its rightmost child.
+                    let child = node.rightmost_child;
+                    self.stack.pop();
+                    child
+                };
+                self.push_node(child, levels_left)?;
+            } else if node.remaining == 0 {
+                self.stack.pop();
+            } else {
+                node.remaining -= 1;
+                node.child_due = node.rightmost_child != 0;
+                return node.block.read_record().map(Some);
+            }
+        }
+    }
+}
+
+impl<'a, 'b> Iterator for Records<'a, 'b> {
+    type Item = Result<(String, &'a str, RecordValue<'a>), Error<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.advance() {
+            Ok(Some(record)) => Some(Ok(record)),
+            Ok(None) => {
+                self.done = true;
+                None
+            },
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            },
+        }
+    }
+}
+
+/// The records read so far while traversing the B-Tree.
+struct TreeRecords<'a> {
+    duplicates: Duplicates,
+    contents: BTreeMap<String, BTreeMap<&'a str, RecordValue<'a>>>,
+    /// The location of each record in `contents`.
+    locations: BTreeMap<(String, &'a str), RecordLocation>,
+    /// Every occurrence of the records read more than once.
+    repeated: BTreeMap<(String, &'a str), Vec<(RecordLocation, RecordValue<'a>)>>,
+}
+
+impl<'a> TreeRecords<'a> {
+    fn insert(&mut self, file_name: String, code: &'a str, value: RecordValue<'a>, location: RecordLocation) -> Result<(), Error<'a>> {
+        let metadata = self.contents.entry(file_name.clone()).or_default();
+        let first = match metadata.get(code) {
+            Some(first) => first,
+            None => {
+                metadata.insert(code, value);
+                self.locations.insert((file_name, code), location);
+                return Ok(());
+            },
+        };
+        if self.duplicates == Duplicates::Reject {
+            return Err(Error::DuplicateRecord(file_name, code));
+        }
+        let key = (file_name, code);
+        let first_location = self.locations[&key];
+        self.repeated.entry(key)
+            .or_insert_with(|| vec![(first_location, first.clone())])
+            .push((location, value));
+        Ok(())
+    }
+}
+
+/// Fills in the alias of picture backgrounds, from the "pict" record next to them.
+fn resolve_background_pictures(contents: &mut BTreeMap<String, BTreeMap<&str, RecordValue>>) {
+    for records in contents.values_mut() {
+        let alias = match records.get("pict") {
+            Some(RecordValue::Alias(record)) => record.value().clone(),
+            _ => continue,
+        };
+        if let Some(RecordValue::Background(BackgroundType::Picture(_, picture))) = records.get_mut("BKGD") {
+            *picture = Some(alias);
+        }
+    }
 }
@@ -0,0 +1,336 @@
+use std::cell::Cell;
+use byteorder::{ByteOrder, BigEndian, LittleEndian};
+use crate::Error;
+
+/// Keys of the entries Finder and CoreFoundation store in bookmarks.
+pub mod keys {
+    /// The URL of the target.
+    pub const URL: u32 = 0x1003;
+    /// An array of path components, from the root down to the target.
+    pub const PATH: u32 = 0x1004;
+    /// An array of file IDs (catalog node IDs), one for each path component.
+    pub const FILE_ID_PATH: u32 = 0x1005;
+    pub const FILE_PROPERTIES: u32 = 0x1010;
+    pub const FILE_NAME: u32 = 0x1020;
+    pub const FILE_ID: u32 = 0x1030;
+    pub const FILE_CREATION_DATE: u32 = 0x1040;
+    pub const VOLUME_PATH: u32 = 0x2002;
+    pub const VOLUME_URL: u32 = 0x2005;
+    pub const VOLUME_NAME: u32 = 0x2010;
+    /// The volume UUID, stored as a string.
+    pub const VOLUME_UUID: u32 = 0x2011;
+    pub const VOLUME_SIZE: u32 = 0x2012;
+    pub const VOLUME_CREATION_DATE: u32 = 0x2013;
+    pub const VOLUME_PROPERTIES: u32 = 0x2020;
+    pub const VOLUME_IS_ROOT: u32 = 0x2030;
+    pub const VOLUME_MOUNT_POINT: u32 = 0x2050;
+    /// The index in `PATH` of the folder containing the target.
+    pub const CONTAINING_FOLDER: u32 = 0xc001;
+    pub const USER_NAME: u32 = 0xc011;
+    pub const UID: u32 = 0xc012;
+    pub const CREATION_OPTIONS: u32 = 0xd010;
+}
+
+/// A macOS bookmark, the successor of Alias records, pointing to a file.
+/// Newer versions of Finder store one in "pBBk" records, pointing to the background picture.
+///
+/// A bookmark is made of tables of contents, each mapping keys to typed items.
+/// See the `keys` module for the keys that are known.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bookmark {
+    /// Each table of contents, with its ID and its entries in the order they are stored.
+    pub tocs: Vec<(u32, Vec<(BookmarkKey, BookmarkItem)>)>,
+}
+
+/// The key of a table of contents entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BookmarkKey {
+    /// One of the numbers in the `keys` module, or an unknown one.
+    Id(u32),
+    Name(String),
+}
+
+/// An item of a bookmark. Numbers are stored in little-endian, dates in big-endian.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BookmarkItem {
+    String(String),
+    Data(Vec<u8>),
+    Integer(i64),
+    Real(f64),
+    /// Seconds since 2001-01-01 00:00:00 UTC, like `Plist::Date`.
+    Date(f64),
+    Boolean(bool),
+    Array(Vec<BookmarkItem>),
+    Dictionary(Vec<(BookmarkItem, BookmarkItem)>),
+    Uuid([u8; 16]),
+    Url(String),
+    /// A URL relative to a base URL.
+    RelativeUrl(Box<BookmarkItem>, Box<BookmarkItem>),
+    Null,
+    /// An item of a type this library does not know, with its type and data.
+    Unknown(u32, Vec<u8>),
+}
+
+impl BookmarkItem {
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            BookmarkItem::String(ref s) | BookmarkItem::Url(ref s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i64> {
+        match *self {
+            BookmarkItem::Integer(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    pub fn as_date(&self) -> Option<f64> {
+        match *self {
+            BookmarkItem::Date(date) => Some(date),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[BookmarkItem]> {
+        match *self {
+            BookmarkItem::Array(ref items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// The URL this item holds, with relative URLs resolved against their base.
+    pub fn as_url(&self) -> Option<String> {
+        match *self {
+            BookmarkItem::Url(ref url) => Some(url.clone()),
+            BookmarkItem::RelativeUrl(ref base, ref relative) => {
+                Some(format!("{}{}", base.as_url()?, relative.as_str()?))
+            },
+            _ => None,
+        }
+    }
+}
+
+impl Bookmark {
+    /// The item stored under `key`, from the first table of contents that has it.
+    pub fn get(&self, key: u32) -> Option<&BookmarkItem> {
+        self.tocs.iter()
+            .flat_map(|toc| toc.1.iter())
+            .find(|entry| entry.0 == BookmarkKey::Id(key))
+            .map(|entry| &entry.1)
+    }
+
+    /// The path components of the target, from the root down.
+    pub fn path(&self) -> Option<Vec<&str>> {
+        self.get(keys::PATH)?.as_array()?.iter().map(BookmarkItem::as_str).collect()
+    }
+
+    /// The absolute POSIX path of the target.
+    pub fn full_path(&self) -> Option<String> {
+        Some(format!("/{}", self.path()?.join("/")))
+    }
+
+    /// The file IDs of each path component.
+    pub fn file_ids(&self) -> Option<Vec<i64>> {
+        self.get(keys::FILE_ID_PATH)?.as_array()?.iter().map(BookmarkItem::as_integer).collect()
+    }
+
+    pub fn creation_date(&self) -> Option<f64> {
+        self.get(keys::FILE_CREATION_DATE)?.as_date()
+    }
+
+    pub fn volume_name(&self) -> Option<&str> {
+        self.get(keys::VOLUME_NAME)?.as_str()
+    }
+
+    pub fn volume_uuid(&self) -> Option<&str> {
+        self.get(keys::VOLUME_UUID)?.as_str()
+    }
+
+    pub fn volume_url(&self) -> Option<String> {
+        self.get(keys::VOLUME_URL)?.as_url()
+    }
+
+    pub fn volume_creation_date(&self) -> Option<f64> {
+        self.get(keys::VOLUME_CREATION_DATE)?.as_date()
+    }
+}
+
+/// A bookmark record value, along with the bytes it was read from, which it is written back as.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookmarkRecord<'a> {
+    value: Bookmark,
+    source: &'a [u8],
+}
+
+impl<'a> BookmarkRecord<'a> {
+    /// Parse a bookmark, keeping `data` to write back.
+    pub fn parse(data: &'a [u8]) -> Result<BookmarkRecord<'a>, Error<'a>> {
+        Ok(BookmarkRecord {value: parse(data)?, source: data})
+    }
+
+    pub fn value(&self) -> &Bookmark {
+        &self.value
+    }
+
+    pub fn into_value(self) -> Bookmark {
+        self.value
+    }
+
+    /// The bytes the value was read from.
+    pub fn source(&self) -> &'a [u8] {
+        self.source
+    }
+}
+
+/// Parse a bookmark.
+///
+/// A bookmark starts with the magic "book", its size, a version and the size of the header.
+/// Every offset after that is relative to the end of the header, which starts with the offset of the first
+/// table of contents. Each table of contents has its size, the magic 0xFFFFFFFE, its ID, the offset of the next one
+/// (0 for the last), and its entries: a key, the offset of the item, and 4 unused bytes.
+/// Keys with the high bit set are the offset of a string item instead.
+pub fn parse<'a>(data: &[u8]) -> Result<Bookmark, Error<'a>> {
+    if data.len() < 16 || &data[..4] != b"book" {
+        return Err(Error::BadData("Not a bookmark."));
+    }
+    let size = LittleEndian::read_u32(&data[4..8]) as usize;
+    let header_size = LittleEndian::read_u32(&data[12..16]) as usize;
+    if size != data.len() || header_size < 16 || header_size + 4 > size {
+        return Err(Error::BadData("Bookmark has an invalid size."));
+    }
+    // An item is read once for every reference to it, and each reference takes 4 bytes,
+    // so only items shared by several arrays or dictionaries can use this up.
+    let parser = Parser {data: &data[header_size..], items_left: Cell::new(data.len())};
+
+    let mut tocs = Vec::new();
+    let mut toc_offset = parser.u32(0)?;
+    while toc_offset != 0 {
+        if tocs.len() > parser.data.len() / 20 {
+            return Err(Error::BadData("Bookmark tables of contents form a cycle."));
+        }
+        let toc = toc_offset as usize;
+        if parser.u32(toc + 4)? != 0xffff_fffe {
+            return Err(Error::BadData("Bookmark table of contents has a bad magic."));
+        }
+        let id = parser.u32(toc + 8)?;
+        let count = parser.u32(toc + 16)? as usize;
+        let mut entries = Vec::new();
+        for n in 0..count {
+            let entry = toc + 20 + 12 * n;
+            let key = parser.u32(entry)?;
+            let key = if key & 0x8000_0000 != 0 {
+                match parser.item(key & 0x7fff_ffff, &mut Vec::new())? {
+                    BookmarkItem::String(name) => BookmarkKey::Name(name),
+                    _ => return Err(Error::BadData("Bookmark key is not a string.")),
+                }
+            } else {
+                BookmarkKey::Id(key)
+            };
+            entries.push((key, parser.item(parser.u32(entry + 4)?, &mut Vec::new())?));
+        }
+        tocs.push((id, entries));
+        toc_offset = parser.u32(toc + 12)?;
+    }
+    Ok(Bookmark {tocs})
+}
+
+/// Reads items from the data after the header.
+struct Parser<'d> {
+    data: &'d [u8],
+    /// How many more items may be read, so that shared arrays and dictionaries can't be expanded without end.
+    items_left: Cell<usize>,
+}
+
+impl<'d> Parser<'d> {
+    fn slice<'a>(&self, start: usize, len: usize) -> Result<&'d [u8], Error<'a>> {
+        match start.checked_add(len) {
+            Some(end) if end <= self.data.len() => Ok(&self.data[start..end]),
+            _ => Err(Error::NotEnoughData),
+        }
+    }
+
+    fn u32<'a>(&self, offset: usize) -> Result<u32, Error<'a>> {
+        Ok(LittleEndian::read_u32(self.slice(offset, 4)?))
+    }
+
+    /// An item is its length, its type (the high 24 bits) and subtype (the low 8 bits), and its data.
+    /// `in_progress` holds the arrays, dictionaries and URLs being read, to reject cycles.
+    fn item<'a>(&self, offset: u32, in_progress: &mut Vec<u32>) -> Result<BookmarkItem, Error<'a>> {
+        if in_progress.contains(&offset) {
+            return Err(Error::BadData("Bookmark item contains itself."));
+        }
+        match self.items_left.get().checked_sub(1) {
+            Some(left) => self.items_left.set(left),
+            None => return Err(Error::BadData("Bookmark refers to its items too many times.")),
+        }
+        let start = offset as usize;
+        let len = self.u32(start)? as usize;
+        let type_code = self.u32(start + 4)?;
+        let data = self.slice(start + 8, len)?;
+        let subtype = type_code & 0xff;
+
+        let item = match (type_code & 0xffff_ff00, subtype) {
+            (0x0100, _) => match std::str::from_utf8(data) {
+                Ok(s) => BookmarkItem::String(s.to_string()),
+                Err(_) => return Err(Error::InvalidString),
+            },
+            (0x0200, _) => BookmarkItem::Data(data.to_vec()),
+            // CFNumber types: SInt8, SInt16, SInt32, SInt64, Float32, Float64.
+            (0x0300, 1) if len == 1 => BookmarkItem::Integer(i64::from(data[0] as i8)),
+            (0x0300, 2) if len == 2 => BookmarkItem::Integer(i64::from(LittleEndian::read_i16(data))),
+            (0x0300, 3) if len == 4 => BookmarkItem::Integer(i64::from(LittleEndian::read_i32(data))),
+            (0x0300, 4) if len == 8 => BookmarkItem::Integer(LittleEndian::read_i64(data)),
+            (0x0300, 5) if len == 4 => BookmarkItem::Real(f64::from(LittleEndian::read_f32(data))),
+            (0x0300, 6) if len == 8 => BookmarkItem::Real(LittleEndian::read_f64(data)),
+            (0x0400, _) if len == 8 => BookmarkItem::Date(BigEndian::read_f64(data)),
+            (0x0500, 0) => BookmarkItem::Boolean(false),
+            (0x0500, 1) => BookmarkItem::Boolean(true),
+            (0x0600, _) | (0x0700, _) | (0x0900, 2) => {
+                let chunks = data.chunks_exact(4);
+                if !chunks.remainder().is_empty() {
+                    return Err(Error::BadData("Bookmark item offsets must be 4 bytes each."));
+                }
+                in_progress.push(offset);
+                let offsets: Vec<u32> = chunks.map(LittleEndian::read_u32).collect();
+                let mut items = Vec::with_capacity(offsets.len());
+                for &item_offset in &offsets {
+                    items.push(self.item(item_offset, in_progress)?);
+                }
+                in_progress.pop();
+
+                match type_code & 0xffff_ff00 {
+                    0x0600 => BookmarkItem::Array(items),
+                    0x0700 => {
+                        let mut items = items.into_iter();
+                        let mut entries = Vec::new();
+                        while let (Some(key), Some(value)) = (items.next(), items.next()) {
+                            entries.push((key, value));
+                        }
+                        BookmarkItem::Dictionary(entries)
+                    },
+                    _ => {
+                        let mut items = items.into_iter();
+                        match (items.next(), items.next(), items.next()) {
+                            (Some(base), Some(relative), None) => BookmarkItem::RelativeUrl(Box::new(base), Box::new(relative)),
+                            _ => return Err(Error::BadData("Relative bookmark URL must have a base and a relative part.")),
+                        }
+                    },
+                }
+            },
+            (0x0800, _) if len == 16 => {
+                let mut uuid = [0; 16];
+                uuid.copy_from_slice(data);
+                BookmarkItem::Uuid(uuid)
+            },
+            (0x0900, 1) => match std::str::from_utf8(data) {
+                Ok(s) => BookmarkItem::Url(s.to_string()),
+                Err(_) => return Err(Error::InvalidString),
+            },
+            (0x0a00, _) => BookmarkItem::Null,
+            _ => BookmarkItem::Unknown(type_code, data.to_vec()),
+        };
+        Ok(item)
+    }
+}
@@ -0,0 +1,104 @@
+use std::cmp::Ordering;
+
+/// Folds a UTF-16 unit like `gLowerCaseTable` in Apple's Technical Note TN1150, returning 0 for characters that are ignored.
+/// The table only covers characters without a canonical decomposition, since HFS+ stores names decomposed:
+/// "É" is stored as "E" and U+0301, which folds like "e" and U+0301, while a precomposed "É" is left as it is.
+fn fold(unit: u16) -> u16 {
+    match unit {
+        // NUL sorts after every other character, instead of ending the name.
+        0x0000 => 0xffff,
+        // Basic Latin and Latin-1, without the precomposed letters.
+        0x0041..=0x005a => unit + 0x20,
+        0x00c6 | 0x00d0 | 0x00d8 | 0x00de => unit + 0x20,
+        // Latin Extended-A.
+        0x0110 | 0x0126 | 0x0132 | 0x013f | 0x0141 | 0x014a | 0x0152 | 0x0166 => unit + 1,
+        // Latin Extended-B.
+        0x0181 => 0x0253,
+        0x0186 => 0x0254,
+        0x0189 => 0x0256,
+        0x018a => 0x0257,
+        0x018e => 0x01dd,
+        0x018f => 0x0259,
+        0x0190 => 0x025b,
+        0x0193 => 0x0260,
+        0x0194 => 0x0263,
+        0x0196 => 0x0269,
+        0x0197 => 0x0268,
+        0x019c => 0x026f,
+        0x019d => 0x0272,
+        0x019f => 0x0275,
+        0x01a9 => 0x0283,
+        0x01ae => 0x0288,
+        0x01b1 => 0x028a,
+        0x01b2 => 0x028b,
+        0x01b7 => 0x0292,
+        0x0182 | 0x0184 | 0x0187 | 0x018b | 0x0191 | 0x0198 | 0x01a2 | 0x01a4 | 0x01a7 | 0x01ac | 0x01b3 | 0x01b5 | 0x01b8
+            | 0x01bc | 0x01e4 => unit + 1,
+        0x01c4 | 0x01c5 => 0x01c6,
+        0x01c7 | 0x01c8 => 0x01c9,
+        0x01ca | 0x01cb => 0x01cc,
+        0x01f1 | 0x01f2 => 0x01f3,
+        // Greek and Coptic.
+        0x0391..=0x03a1 | 0x03a3..=0x03a9 => unit + 0x20,
+        0x03e2..=0x03ee if unit % 2 == 0 => unit + 1,
+        // Cyrillic.
+        0x0402 | 0x0404..=0x0406 | 0x0408..=0x040b | 0x040f => unit + 0x50,
+        // Except "Й", which decomposes.
+        0x0410..=0x0418 | 0x041a..=0x042f => unit + 0x20,
+        0x0460..=0x0474 | 0x0478..=0x0480 | 0x0490..=0x04be if unit % 2 == 0 => unit + 1,
+        0x04c3 | 0x04c7 | 0x04cb => unit + 1,
+        0x04d4 | 0x04d8 | 0x04e0 | 0x04e8 => unit + 1,
+        // Armenian.
+        0x0531..=0x0556 => unit + 0x30,
+        // Georgian.
+        0x10a0..=0x10c5 => unit + 0x30,
+        // Zero-width joiners, directional marks and other format characters.
+        0x200c..=0x200f | 0x202a..=0x202e | 0x206a..=0x206f | 0xfeff => 0,
+        // Roman numerals.
+        0x2160..=0x216f => unit + 0x10,
+        // Fullwidth Latin.
+        0xff21..=0xff3a => unit + 0x20,
+        _ => unit,
+    }
+}
+
+/// The next folded unit of `units` that is not ignored, or 0 at the end of the name.
+fn next_folded<I: Iterator<Item = u16>>(units: &mut I) -> u16 {
+    units.map(fold).find(|&unit| unit != 0).unwrap_or(0)
+}
+
+/// Compares two names given as UTF-16, the way HFS+ does (`FastUnicodeCompare` in TN1150).
+/// Names that only differ in case or in ignored characters are equal, and a name sorts before any longer name it starts.
+pub fn fast_unicode_compare(left: &[u16], right: &[u16]) -> Ordering {
+    compare_units(left.iter().cloned(), right.iter().cloned())
+}
+
+fn compare_units<L: Iterator<Item = u16>, R: Iterator<Item = u16>>(mut left: L, mut right: R) -> Ordering {
+    loop {
+        let l = next_folded(&mut left);
+        let r = next_folded(&mut right);
+        if l != r {
+            return l.cmp(&r);
+        }
+        if l == 0 {
+            return Ordering::Equal;
+        }
+    }
+}
+
+/// Compares two filenames the way the B-Tree of a .DS_Store file is sorted.
+pub fn compare_file_names(left: &str, right: &str) -> Ordering {
+    compare_units(left.encode_utf16(), right.encode_utf16())
+}
+
+/// The units `compare_file_names` compares `name` by: two names are equal exactly when these are.
+pub fn folded_name(name: &str) -> Vec<u16> {
+    name.encode_utf16().map(fold).filter(|&unit| unit != 0).collect()
+}
+
+/// Compares two (filename, code) record keys the way the B-Tree of a .DS_Store file is sorted:
+/// by filename with `compare_file_names`, and then by code, byte by byte.
+pub fn compare_keys(left: (&str, &str), right: (&str, &str)) -> Ordering {
+    compare_file_names(left.0, right.0)
+        .then_with(|| left.1.cmp(right.1))
+}
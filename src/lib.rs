@@ -2,10 +2,24 @@
 extern crate byteorder;
 extern crate chrono;
 
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use crate::allocator::{Allocator};
-pub use crate::allocator::{Directory, RecordValue};
+use crate::hfs::{compare_keys, folded_name};
+pub use crate::allocator::{BackgroundType, DesktopCoordinate, DesktopIconLocation, Directory, DuplicateRecord, Duplicates, FinderInfo,
+                           RecordValue, GenericValue, IconLocation, LegacyIconViewOptions, RecordLocation, Timestamp, WindowInfo};
+pub use crate::alias::{Alias, AliasRecord};
+pub use crate::bookmark::{Bookmark, BookmarkRecord};
+pub use crate::plist::{Plist, PlistRecord};
+pub use crate::views::{Arrangement, BrowserWindowSettings, Column, ColumnLayout, IconViewBackground, IconViewOptions,
+                       KeyOrder, LabelPosition, LegacyUpgrade, ListViewSettings, Rect, UpgradeOutcome};
+pub mod alias;
 pub mod allocator;
+pub mod bookmark;
+pub mod hfs;
+pub mod plist;
+pub mod views;
+mod writer;
 
 
 // TODO: Better errors NotEnoughData, and InvalidString could all take a &'static str, describing their errors.
@@ -17,21 +31,304 @@ pub enum Error<'a> {
     BlockDoesntExist,
     InvalidString,
     UnkonwnStructureType(&'a [u8]),
-    UnsupportedStructureType(&'a [u8])
+    UnsupportedStructureType(&'a [u8]),
+    /// A filename and code stored more than once, read with `Duplicates::Reject`.
+    DuplicateRecord(String, &'a str),
 }
 
 pub struct DsStore<'a> {
     directory: Directory<'a>,
+    /// The allocator the store was read with, if it was read from a file.
+    allocator: Option<Allocator<'a>>,
+    /// The name each file is stored under in `directory`, by its `hfs::folded_name`.
+    file_keys: HashMap<Vec<u16>, String>,
+}
+
+impl<'a> Default for DsStore<'a> {
+    /// A store without any records, to be written out with `to_bytes`.
+    fn default() -> DsStore<'a> {
+        DsStore::from_directory(Directory::new(BTreeMap::new()), None)
+    }
 }
 
 impl<'a> DsStore<'a> {
+    /// Read a store, keeping records stored more than once as `Duplicates::Keep` does.
     pub fn new(file_data: &'a [u8]) -> Result<DsStore<'a>, Error<'a>> {
+        DsStore::with_duplicates(file_data, Duplicates::Keep)
+    }
+
+    /// Read a store, handling records stored more than once as `duplicates` says.
+    pub fn with_duplicates(file_data: &'a [u8], duplicates: Duplicates) -> Result<DsStore<'a>, Error<'a>> {
         let allocator = Allocator::new(file_data)?;
-        let contents: Directory<'a> = allocator.traverse()?;
-        Ok(DsStore {directory: contents})
+        let contents: Directory<'a> = allocator.traverse_with(duplicates)?;
+        Ok(DsStore::from_directory(contents, Some(allocator)))
+    }
+
+    /// Create a store from scratch, to be written out with `to_bytes`.
+    /// `contents` maps each file to its records, like a `HashMap` or `BTreeMap` of them.
+    /// `to_bytes` fails if two of the names only differ in case, since the B-Tree can't tell them apart.
+    pub fn from_contents<F, R>(contents: F) -> DsStore<'a>
+        where F: IntoIterator<Item = (String, R)>, R: IntoIterator<Item = (&'a str, RecordValue<'a>)> {
+        let contents = contents.into_iter()
+            .map(|(file_name, records)| (file_name, records.into_iter().collect()))
+            .collect();
+        DsStore::from_directory(Directory::new(contents), None)
     }
 
-    pub fn contents(&self) -> &HashMap<String, HashMap<&'a str, RecordValue<'a>>> {
+    fn from_directory(directory: Directory<'a>, allocator: Option<Allocator<'a>>) -> DsStore<'a> {
+        let file_keys = directory.contents.keys().map(|file_name| (folded_name(file_name), file_name.clone())).collect();
+        DsStore {directory, allocator, file_keys}
+    }
+
+    /// Every file and its records, ordered by filename and then code.
+    /// Those are compared byte by byte, unlike in the B-Tree: see `records` for that order.
+    pub fn contents(&self) -> &BTreeMap<String, BTreeMap<&'a str, RecordValue<'a>>> {
         &self.directory.contents
     }
+
+    /// Every record as (filename, code, value), in the order they are stored in the B-Tree of the file the store was read from.
+    /// Records added since then, and the records of a store that was not read, are placed in the order `to_bytes` writes them in,
+    /// which is the order Finder keeps its files in.
+    pub fn records(&self) -> Vec<(&str, &'a str, &RecordValue<'a>)> {
+        let mut stored = Vec::new();
+        let mut seen = HashSet::new();
+        for (file_name, code) in self.directory.stored_keys() {
+            let record = self.directory.contents.get_key_value(file_name)
+                .and_then(|(file_name, metadata)| metadata.get_key_value(code).map(|(code, value)| (file_name.as_str(), *code, value)));
+            if let Some(record) = record {
+                if seen.insert((record.0, record.1)) {
+                    stored.push(record);
+                }
+            }
+        }
+
+        let mut added = writer::sorted_records(&self.directory.contents).into_iter()
+            .filter(|&(file_name, code, _)| !seen.contains(&(file_name, code)))
+            .peekable();
+        let mut records = Vec::with_capacity(stored.len());
+        for record in stored {
+            while let Some(next) = added.next_if(|next| compare_keys((next.0, next.1), (record.0, record.1)) == Ordering::Less) {
+                records.push(next);
+            }
+            records.push(record);
+        }
+        records.extend(added);
+        records
+    }
+
+    /// The records that were stored more than once in the file, with every occurrence and where it was.
+    /// `contents` and `records` only hold the first occurrence, and `to_bytes` only writes that one.
+    pub fn duplicates(&self) -> &[DuplicateRecord<'a>] {
+        &self.directory.duplicates
+    }
+
+    /// The records of `file_name`, added if it has none. The B-Tree can't tell apart names that only differ in case,
+    /// so those are the same file, and keep the name it already has.
+    fn file_records(&mut self, file_name: &str) -> &mut BTreeMap<&'a str, RecordValue<'a>> {
+        if self.directory.contents.contains_key(file_name) {
+            return self.directory.contents.get_mut(file_name).unwrap();
+        }
+        let file_key = self.file_keys.entry(folded_name(file_name)).or_insert_with(|| file_name.to_string());
+        self.directory.contents.entry(file_key.clone()).or_default()
+    }
+
+    /// Remove every record of the file stored as `file_name`.
+    fn remove_file_key(&mut self, file_name: &str) -> Option<BTreeMap<&'a str, RecordValue<'a>>> {
+        let removed = self.directory.contents.remove(file_name)?;
+        let folded = folded_name(file_name);
+        if self.file_keys.get(&folded).map(String::as_str) == Some(file_name) {
+            self.file_keys.remove(&folded);
+        }
+        Some(removed)
+    }
+
+    /// Set the `structure_type` record of `file_name` to `value`, returning the previous value.
+    /// If a file whose name only differs in case already has records, the record is set on that file.
+    /// Fails without changing anything if `value` is not the type of value that `structure_type` takes.
+    pub fn set_record(&mut self, file_name: &str, structure_type: &'a str, value: RecordValue<'a>) -> Result<Option<RecordValue<'a>>, Error<'a>> {
+        writer::check_record(structure_type, &value)?;
+        Ok(self.file_records(file_name).insert(structure_type, value))
+    }
+
+    /// Remove the `structure_type` record of `file_name`, returning it if it existed.
+    pub fn remove_record(&mut self, file_name: &str, structure_type: &str) -> Option<RecordValue<'a>> {
+        let (removed, now_empty) = {
+            let metadata = self.directory.contents.get_mut(file_name)?;
+            (metadata.remove(structure_type), metadata.is_empty())
+        };
+        if now_empty {
+            self.remove_file_key(file_name);
+        }
+        removed
+    }
+
+    /// Remove every record of `file_name`, returning them if there were any.
+    pub fn remove_file(&mut self, file_name: &str) -> Option<BTreeMap<&'a str, RecordValue<'a>>> {
+        self.remove_file_key(file_name)
+    }
+
+    /// Move every record of `from` to `to`, replacing any records `to` already had,
+    /// or that a file whose name only differs from `to` in case had.
+    /// Returns false if `from` has no records.
+    pub fn rename_file(&mut self, from: &str, to: &str) -> bool {
+        match self.remove_file_key(from) {
+            Some(metadata) => {
+                if let Some(replaced) = self.file_keys.insert(folded_name(to), to.to_string()) {
+                    self.directory.contents.remove(&replaced);
+                }
+                self.directory.contents.insert(to.to_string(), metadata);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Where the icon of `file_name` is placed in icon view, if it has been placed.
+    pub fn icon_location(&self, file_name: &str) -> Option<IconLocation> {
+        match self.directory.contents.get(file_name)?.get("Iloc") {
+            Some(&RecordValue::IconLocation(location)) => Some(location),
+            _ => None,
+        }
+    }
+
+    /// Where the icon of `file_name` is placed on the desktop, if this is the store of a desktop.
+    pub fn desktop_icon_location(&self, file_name: &str) -> Option<DesktopIconLocation> {
+        match self.directory.contents.get(file_name)?.get("dilc") {
+            Some(&RecordValue::DesktopIconLocation(location)) => Some(location),
+            _ => None,
+        }
+    }
+
+    /// Place the icon of `file_name` at (`x`, `y`) in icon view,
+    /// keeping the unknown trailing bytes if the icon was already placed.
+    pub fn set_icon_location(&mut self, file_name: &str, x: u32, y: u32) {
+        let records = self.file_records(file_name);
+        let location = match records.get("Iloc") {
+            Some(&RecordValue::IconLocation(location)) => IconLocation {x, y, ..location},
+            _ => IconLocation::new(x, y),
+        };
+        records.insert("Iloc", RecordValue::IconLocation(location));
+    }
+
+    /// The plist stored in the `structure_type` record of `file_name`, if there is one.
+    fn plist(&self, file_name: &str, structure_type: &str) -> Option<&Plist> {
+        match self.directory.contents.get(file_name)?.get(structure_type) {
+            Some(RecordValue::Plist(record)) => Some(record.value()),
+            _ => None,
+        }
+    }
+
+    fn set_plist(&mut self, file_name: &str, structure_type: &'a str, plist: Plist) {
+        self.file_records(file_name)
+            .insert(structure_type, RecordValue::Plist(PlistRecord::new(plist)));
+    }
+
+    /// The Finder window settings of the directory `file_name` (usually "."), from its "bwsp" record.
+    pub fn browser_window_settings(&self, file_name: &str) -> Option<BrowserWindowSettings> {
+        BrowserWindowSettings::from_plist(self.plist(file_name, "bwsp")?).ok()
+    }
+
+    pub fn set_browser_window_settings(&mut self, file_name: &str, settings: &BrowserWindowSettings) {
+        self.set_plist(file_name, "bwsp", settings.to_plist());
+    }
+
+    /// The icon view options of the directory `file_name` (usually "."), from its "icvp" record.
+    pub fn icon_view_options(&self, file_name: &str) -> Option<IconViewOptions> {
+        IconViewOptions::from_plist(self.plist(file_name, "icvp")?).ok()
+    }
+
+    pub fn set_icon_view_options(&mut self, file_name: &str, options: &IconViewOptions) {
+        self.set_plist(file_name, "icvp", options.to_plist());
+    }
+
+    /// The list view settings of the directory `file_name` (usually "."),
+    /// from its `structure_type` record, either "lsvp" or "lsvP".
+    pub fn list_view_settings(&self, file_name: &str, structure_type: &str) -> Option<ListViewSettings> {
+        match structure_type {
+            "lsvp" | "lsvP" => ListViewSettings::from_plist(self.plist(file_name, structure_type)?).ok(),
+            _ => None,
+        }
+    }
+
+    /// Set the `structure_type` record of `file_name`, which must be either "lsvp" or "lsvP".
+    pub fn set_list_view_settings(&mut self, file_name: &str, structure_type: &'a str, settings: &ListViewSettings) -> Result<(), Error<'a>> {
+        match structure_type {
+            "lsvp" | "lsvP" => {
+                self.set_plist(file_name, structure_type, settings.to_plist());
+                Ok(())
+            },
+            _ => Err(Error::BadData("List view settings are only stored in \"lsvp\" and \"lsvP\".")),
+        }
+    }
+
+    /// Add the modern equivalents of legacy view records, which Finder ignores since Mac OS X 10.6:
+    /// "icvp" from "icvo", "vstl" from "fwi0", and "lsvp" from "lsvt".
+    /// A modern record that already exists always wins, and the legacy records are left in place.
+    /// Only the text size of list view is carried over to "lsvp": the rest of the legacy list view options
+    /// are in "lsvo", whose layout is not known.
+    /// The window rectangle of "fwi0" is not carried over to "bwsp": how its QuickDraw coordinates map to
+    /// the "WindowBounds" of "bwsp" has not been checked against a pair of records written by Finder.
+    /// Returns what happened to each modern record, sorted by directory.
+    pub fn upgrade_legacy_views(&mut self) -> Vec<LegacyUpgrade> {
+        let mut upgrades = Vec::new();
+        for (file_name, records) in self.directory.contents.iter_mut() {
+            let mut modern = Vec::new();
+            if let Some(RecordValue::LegacyIconView(options)) = records.get("icvo") {
+                let plist = IconViewOptions::from_legacy(options).to_plist();
+                modern.push(("icvo", "icvp", RecordValue::Plist(PlistRecord::new(plist))));
+            }
+            if let Some(RecordValue::WindowInfo(info)) = records.get("fwi0") {
+                if let Some(style) = info.style() {
+                    modern.push(("fwi0", "vstl", RecordValue::Style(style)));
+                }
+            }
+            if let Some(&RecordValue::I16(text_size)) = records.get("lsvt") {
+                let plist = ListViewSettings::from_text_size(text_size).to_plist();
+                modern.push(("lsvt", "lsvp", RecordValue::Plist(PlistRecord::new(plist))));
+            }
+
+            for (from, to, value) in modern {
+                // List view settings are also stored in "lsvP", so either one is a modern record.
+                let outcome = if records.contains_key(to) || (to == "lsvp" && records.contains_key("lsvP")) {
+                    UpgradeOutcome::ModernRecordKept
+                } else {
+                    records.insert(to, value);
+                    UpgradeOutcome::Added
+                };
+                upgrades.push(LegacyUpgrade {file_name: file_name.clone(), from, to, outcome});
+            }
+        }
+        upgrades.sort_by(|l, r| l.file_name.cmp(&r.file_name));
+        upgrades
+    }
+
+    /// Give the directory `file_name` (usually ".") a picture background, showing the file `alias` points to.
+    /// This sets its "BKGD" and "pict" records, and the background of its icon view options, which newer Finders use.
+    pub fn set_background_picture(&mut self, file_name: &str, alias: Alias) {
+        let data = alias::write(&alias);
+        let mut options = self.icon_view_options(file_name).unwrap_or_default();
+        options.background_type = Some(IconViewBackground::Picture);
+        options.background_image_alias = Some(data);
+        self.set_icon_view_options(file_name, &options);
+
+        let records = self.file_records(file_name);
+        records.insert("BKGD", RecordValue::Background(BackgroundType::picture(alias.clone())));
+        records.insert("pict", RecordValue::Alias(AliasRecord::new(alias)));
+    }
+
+    /// Serialize the store into the bytes of a complete `.DS_Store` file.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error<'a>> {
+        writer::write(&self.directory.contents)
+    }
+
+    /// Serialize the store into the exact layout it was read from.
+    /// If nothing was changed, this gives back the bytes the store was created from.
+    /// Fails for stores made with `from_contents`, or when records were added or removed,
+    /// in which case `to_bytes` lays the file out anew.
+    pub fn to_bytes_lossless(&self) -> Result<Vec<u8>, Error<'a>> {
+        match self.allocator {
+            Some(ref allocator) => writer::write_lossless(allocator, &self.directory),
+            None => Err(Error::BadData("Only a store read from a file can be written losslessly.")),
+        }
+    }
 }
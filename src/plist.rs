@@ -0,0 +1,469 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use byteorder::{ByteOrder, BigEndian};
+use crate::Error;
+
+/// A value of an Apple property list.
+/// Finder stores "bwsp", "icvp", "lsvp" and "lsvP" records as binary property lists ("bplist00").
+#[derive(Debug, Clone, PartialEq)]
+pub enum Plist {
+    /// Entries in the order they are stored.
+    Dictionary(Vec<(String, Plist)>),
+    Array(Vec<Plist>),
+    String(String),
+    Integer(i64),
+    Real(f64),
+    Boolean(bool),
+    Data(Vec<u8>),
+    /// Seconds since 2001-01-01 00:00:00 UTC.
+    Date(f64),
+    Uid(u64),
+}
+
+impl Plist {
+    /// The value of `key`, if this is a dictionary containing it.
+    pub fn get(&self, key: &str) -> Option<&Plist> {
+        match *self {
+            Plist::Dictionary(ref entries) => entries.iter().find(|e| e.0 == key).map(|e| &e.1),
+            _ => None,
+        }
+    }
+
+    pub fn as_dictionary(&self) -> Option<&[(String, Plist)]> {
+        match *self {
+            Plist::Dictionary(ref entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Plist]> {
+        match *self {
+            Plist::Array(ref values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Plist::String(ref s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i64> {
+        match *self {
+            Plist::Integer(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    /// The value as a float. Integers are converted, as Finder writes some numbers either way.
+    pub fn as_real(&self) -> Option<f64> {
+        match *self {
+            Plist::Real(n) => Some(n),
+            Plist::Integer(n) => Some(n as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Plist::Boolean(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn as_data(&self) -> Option<&[u8]> {
+        match *self {
+            Plist::Data(ref data) => Some(data),
+            _ => None,
+        }
+    }
+}
+
+/// A plist record value, along with the bytes it was read from.
+/// Values read from a file are written back as those exact bytes, other values are written with `write`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlistRecord<'a> {
+    value: Plist,
+    source: Option<&'a [u8]>,
+}
+
+impl<'a> PlistRecord<'a> {
+    pub fn new(value: Plist) -> PlistRecord<'a> {
+        PlistRecord {value, source: None}
+    }
+
+    /// Parse a binary property list, keeping `data` to write back.
+    pub fn parse(data: &'a [u8]) -> Result<PlistRecord<'a>, Error<'a>> {
+        Ok(PlistRecord {value: parse(data)?, source: Some(data)})
+    }
+
+    pub fn value(&self) -> &Plist {
+        &self.value
+    }
+
+    pub fn into_value(self) -> Plist {
+        self.value
+    }
+
+    /// The bytes the value was read from, if it was read from a file.
+    pub fn source(&self) -> Option<&'a [u8]> {
+        self.source
+    }
+}
+
+/// Parse a binary property list.
+pub fn parse<'a>(data: &'a [u8]) -> Result<Plist, Error<'a>> {
+    if data.len() < 8 + 32 || &data[..8] != b"bplist00" {
+        return Err(Error::BadData("Not a binary property list."));
+    }
+    // The trailer: 6 unused bytes, the size of each offset and object reference,
+    // the number of objects, the top object, and where the offset table starts.
+    let trailer = &data[data.len() - 32..];
+    let offset_size = trailer[6] as usize;
+    let ref_size = trailer[7] as usize;
+    let num_objects = BigEndian::read_u64(&trailer[8..16]);
+    let top_object = BigEndian::read_u64(&trailer[16..24]);
+    let offset_table = BigEndian::read_u64(&trailer[24..32]);
+
+    if offset_size == 0 || offset_size > 8 || ref_size == 0 || ref_size > 8 {
+        return Err(Error::BadData("Property list has invalid integer sizes."));
+    }
+    let table_end = (num_objects as u128) * (offset_size as u128) + offset_table as u128;
+    if table_end > (data.len() - 32) as u128 {
+        return Err(Error::NotEnoughData);
+    }
+    // An object is decoded once for every reference to it. Arrays and dictionaries are not shared
+    // in the property lists Finder writes, so those have fewer references than bytes.
+    let parser = Parser {data, offset_size, ref_size, offset_table: offset_table as usize, num_objects, objects_left: Cell::new(data.len())};
+    parser.read_object(top_object, &mut Vec::new())
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    offset_size: usize,
+    ref_size: usize,
+    offset_table: usize,
+    num_objects: u64,
+    /// How many more objects may be decoded, so that shared arrays and dictionaries can't be expanded without end.
+    objects_left: Cell<usize>,
+}
+
+impl<'a> Parser<'a> {
+    fn slice(&self, start: usize, len: usize) -> Result<&'a [u8], Error<'a>> {
+        match start.checked_add(len) {
+            Some(end) if end <= self.data.len() => Ok(&self.data[start..end]),
+            _ => Err(Error::NotEnoughData),
+        }
+    }
+
+    fn object_offset(&self, object: u64) -> Result<usize, Error<'a>> {
+        if object >= self.num_objects {
+            return Err(Error::BadData("Property list refers to an object that does not exist."));
+        }
+        let entry = self.slice(self.offset_table + object as usize * self.offset_size, self.offset_size)?;
+        Ok(BigEndian::read_uint(entry, self.offset_size) as usize)
+    }
+
+    /// Reads the length stored in the low nibble of a marker,
+    /// or in the integer object following it if the nibble is 0xF.
+    /// Returns the length and where the content starts.
+    fn read_length(&self, marker_offset: usize) -> Result<(usize, usize), Error<'a>> {
+        let low = self.slice(marker_offset, 1)?[0] & 0xf;
+        if low != 0xf {
+            return Ok((low as usize, marker_offset + 1));
+        }
+        let int_marker = self.slice(marker_offset + 1, 1)?[0];
+        if int_marker >> 4 != 0x1 {
+            return Err(Error::BadData("Property list length is not an integer."));
+        }
+        let size = 1 << (int_marker & 0xf);
+        if size > 8 {
+            return Err(Error::BadData("Property list length is too large."));
+        }
+        let length = BigEndian::read_uint(self.slice(marker_offset + 2, size)?, size);
+        Ok((length as usize, marker_offset + 2 + size))
+    }
+
+    fn read_refs(&self, start: usize, count: usize) -> Result<Vec<u64>, Error<'a>> {
+        let len = match count.checked_mul(self.ref_size) {
+            Some(len) => len,
+            None => return Err(Error::NotEnoughData),
+        };
+        let refs = self.slice(start, len)?;
+        Ok(refs.chunks(self.ref_size).map(|r| BigEndian::read_uint(r, self.ref_size)).collect())
+    }
+
+    /// Reads an object. `parents` holds the containers being read, to reject cycles.
+    fn read_object(&self, object: u64, parents: &mut Vec<u64>) -> Result<Plist, Error<'a>> {
+        match self.objects_left.get().checked_sub(1) {
+            Some(left) => self.objects_left.set(left),
+            None => return Err(Error::BadData("Property list refers to its objects too many times.")),
+        }
+        let offset = self.object_offset(object)?;
+        let marker = self.slice(offset, 1)?[0];
+        match marker >> 4 {
+            0x0 => match marker {
+                0x08 => Ok(Plist::Boolean(false)),
+                0x09 => Ok(Plist::Boolean(true)),
+                _ => Err(Error::BadData("Unsupported property list object.")),
+            },
+            0x1 => {
+                let size = 1 << (marker & 0xf);
+                match size {
+                    1 | 2 | 4 => Ok(Plist::Integer(BigEndian::read_uint(self.slice(offset + 1, size)?, size) as i64)),
+                    8 => Ok(Plist::Integer(BigEndian::read_i64(self.slice(offset + 1, 8)?))),
+                    // 128-bit integers only hold unsigned 64-bit values, in their lower half.
+                    16 => Ok(Plist::Integer(BigEndian::read_u64(self.slice(offset + 9, 8)?) as i64)),
+                    _ => Err(Error::BadData("Invalid property list integer size.")),
+                }
+            },
+            0x2 => match marker & 0xf {
+                2 => Ok(Plist::Real(f64::from(BigEndian::read_f32(self.slice(offset + 1, 4)?)))),
+                3 => Ok(Plist::Real(BigEndian::read_f64(self.slice(offset + 1, 8)?))),
+                _ => Err(Error::BadData("Invalid property list real size.")),
+            },
+            0x3 if marker == 0x33 => Ok(Plist::Date(BigEndian::read_f64(self.slice(offset + 1, 8)?))),
+            0x4 => {
+                let (length, start) = self.read_length(offset)?;
+                Ok(Plist::Data(self.slice(start, length)?.to_vec()))
+            },
+            0x5 => {
+                let (length, start) = self.read_length(offset)?;
+                match std::str::from_utf8(self.slice(start, length)?) {
+                    Ok(s) => Ok(Plist::String(s.to_string())),
+                    Err(_) => Err(Error::InvalidString),
+                }
+            },
+            0x6 => {
+                let (length, start) = self.read_length(offset)?;
+                let bytes = self.slice(start, length.checked_mul(2).ok_or(Error::NotEnoughData)?)?;
+                let units: Vec<u16> = bytes.chunks(2).map(BigEndian::read_u16).collect();
+                match String::from_utf16(&units) {
+                    Ok(s) => Ok(Plist::String(s)),
+                    Err(_) => Err(Error::InvalidString),
+                }
+            },
+            0x8 => {
+                let size = (marker & 0xf) as usize + 1;
+                if size > 8 {
+                    return Err(Error::BadData("Property list UID is too large."));
+                }
+                Ok(Plist::Uid(BigEndian::read_uint(self.slice(offset + 1, size)?, size)))
+            },
+            0xA => {
+                let (count, start) = self.read_length(offset)?;
+                let refs = self.read_refs(start, count)?;
+                self.enter(object, parents)?;
+                let mut values = Vec::with_capacity(count);
+                for r in refs {
+                    values.push(self.read_object(r, parents)?);
+                }
+                parents.pop();
+                Ok(Plist::Array(values))
+            },
+            0xD => {
+                let (count, start) = self.read_length(offset)?;
+                // All the key references come first, then all the value references.
+                let refs = self.read_refs(start, count.checked_mul(2).ok_or(Error::NotEnoughData)?)?;
+                self.enter(object, parents)?;
+                let mut entries = Vec::with_capacity(count);
+                for i in 0..count {
+                    let key = match self.read_object(refs[i], parents)? {
+                        Plist::String(key) => key,
+                        _ => return Err(Error::BadData("Property list dictionary keys must be strings.")),
+                    };
+                    entries.push((key, self.read_object(refs[count + i], parents)?));
+                }
+                parents.pop();
+                Ok(Plist::Dictionary(entries))
+            },
+            _ => Err(Error::BadData("Unsupported property list object.")),
+        }
+    }
+
+    fn enter(&self, object: u64, parents: &mut Vec<u64>) -> Result<(), Error<'a>> {
+        if parents.contains(&object) {
+            return Err(Error::BadData("Property list contains itself."));
+        }
+        parents.push(object);
+        Ok(())
+    }
+}
+
+/// Write a binary property list.
+///
+/// Like Finder, every object but arrays and dictionaries is only written once,
+/// objects are ordered parents first with dictionary keys before values,
+/// and offsets and object references use as few bytes as they can.
+pub fn write(value: &Plist) -> Vec<u8> {
+    let mut flattener = Flattener {objects: Vec::new(), unique: HashMap::new()};
+    let top_object = flattener.flatten(value);
+    let objects = flattener.objects;
+    let ref_size = size_needed(objects.len() as u64);
+
+    let mut out = b"bplist00".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for object in &objects {
+        offsets.push(out.len() as u64);
+        match *object {
+            Object::Scalar(ref bytes) => out.extend_from_slice(bytes),
+            Object::Array(ref refs) => {
+                write_marker(&mut out, 0xA, refs.len());
+                for &r in refs {
+                    write_uint(&mut out, r, ref_size);
+                }
+            },
+            Object::Dictionary(ref keys, ref values) => {
+                write_marker(&mut out, 0xD, keys.len());
+                for &r in keys.iter().chain(values) {
+                    write_uint(&mut out, r, ref_size);
+                }
+            },
+        }
+    }
+
+    let offset_table = out.len() as u64;
+    let offset_size = size_needed(offset_table);
+    for offset in offsets {
+        write_uint(&mut out, offset, offset_size);
+    }
+    out.extend_from_slice(&[0; 6]);
+    out.push(offset_size as u8);
+    out.push(ref_size as u8);
+    write_uint(&mut out, objects.len() as u64, 8);
+    write_uint(&mut out, top_object, 8);
+    write_uint(&mut out, offset_table, 8);
+    out
+}
+
+/// An object of the plist being written. Containers refer to other objects by index.
+enum Object {
+    Scalar(Vec<u8>),
+    Array(Vec<u64>),
+    Dictionary(Vec<u64>, Vec<u64>),
+}
+
+struct Flattener {
+    objects: Vec<Object>,
+    /// Index of every scalar already written, by its encoding.
+    unique: HashMap<Vec<u8>, u64>,
+}
+
+impl Flattener {
+    /// Adds `value` and everything it contains to `objects`, returning its index.
+    fn flatten(&mut self, value: &Plist) -> u64 {
+        match *value {
+            Plist::Array(ref values) => {
+                let index = self.reserve();
+                let refs = values.iter().map(|v| self.flatten(v)).collect();
+                self.objects[index as usize] = Object::Array(refs);
+                index
+            },
+            Plist::Dictionary(ref entries) => {
+                let index = self.reserve();
+                let keys = entries.iter().map(|e| self.add_scalar(encode_string(&e.0))).collect();
+                let values = entries.iter().map(|e| self.flatten(&e.1)).collect();
+                self.objects[index as usize] = Object::Dictionary(keys, values);
+                index
+            },
+            _ => self.add_scalar(encode_scalar(value)),
+        }
+    }
+
+    fn reserve(&mut self) -> u64 {
+        self.objects.push(Object::Array(Vec::new()));
+        self.objects.len() as u64 - 1
+    }
+
+    fn add_scalar(&mut self, bytes: Vec<u8>) -> u64 {
+        if let Some(&index) = self.unique.get(&bytes) {
+            return index;
+        }
+        let index = self.objects.len() as u64;
+        self.unique.insert(bytes.clone(), index);
+        self.objects.push(Object::Scalar(bytes));
+        index
+    }
+}
+
+/// The number of bytes (1, 2, 4 or 8) needed to store `n`.
+fn size_needed(n: u64) -> usize {
+    if n < 1 << 8 {
+        1
+    } else if n < 1 << 16 {
+        2
+    } else if n < 1 << 32 {
+        4
+    } else {
+        8
+    }
+}
+
+fn write_uint(out: &mut Vec<u8>, n: u64, size: usize) {
+    let mut buf = [0; 8];
+    BigEndian::write_uint(&mut buf, n, size);
+    out.extend_from_slice(&buf[..size]);
+}
+
+/// Writes a marker with the length in its low nibble, or in an integer object following it.
+fn write_marker(out: &mut Vec<u8>, kind: u8, length: usize) {
+    if length < 0xf {
+        out.push(kind << 4 | length as u8);
+    } else {
+        out.push(kind << 4 | 0xf);
+        out.extend_from_slice(&encode_integer(length as i64));
+    }
+}
+
+fn encode_integer(n: i64) -> Vec<u8> {
+    // Negative numbers always take 8 bytes.
+    let size = if n < 0 { 8 } else { size_needed(n as u64) };
+    let mut out = vec![0x10 | size.trailing_zeros() as u8];
+    write_uint(&mut out, n as u64, size);
+    out
+}
+
+fn encode_string(s: &str) -> Vec<u8> {
+    let mut out = Vec::new();
+    if s.is_ascii() {
+        write_marker(&mut out, 0x5, s.len());
+        out.extend_from_slice(s.as_bytes());
+    } else {
+        let units: Vec<u16> = s.encode_utf16().collect();
+        write_marker(&mut out, 0x6, units.len());
+        for unit in units {
+            write_uint(&mut out, u64::from(unit), 2);
+        }
+    }
+    out
+}
+
+fn encode_scalar(value: &Plist) -> Vec<u8> {
+    let mut out = Vec::new();
+    match *value {
+        Plist::Boolean(b) => out.push(if b { 0x09 } else { 0x08 }),
+        Plist::Integer(n) => out = encode_integer(n),
+        Plist::Real(n) => {
+            out.push(0x23);
+            write_uint(&mut out, n.to_bits(), 8);
+        },
+        Plist::Date(n) => {
+            out.push(0x33);
+            write_uint(&mut out, n.to_bits(), 8);
+        },
+        Plist::Data(ref data) => {
+            write_marker(&mut out, 0x4, data.len());
+            out.extend_from_slice(data);
+        },
+        Plist::String(ref s) => out = encode_string(s),
+        Plist::Uid(n) => {
+            let size = size_needed(n);
+            out.push(0x80 | (size - 1) as u8);
+            write_uint(&mut out, n, size);
+        },
+        Plist::Array(_) | Plist::Dictionary(_) => unreachable!("containers are not scalars"),
+    }
+    out
+}
@@ -0,0 +1,578 @@
+use std::fmt;
+use crate::Error;
+use crate::plist::Plist;
+use crate::allocator::LegacyIconViewOptions;
+
+/// The order of the keys of a plist dictionary, so writing it back puts every key where it was.
+/// The settings that hold one don't compare it: settings are equal when their entries are, wherever those are.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct KeyOrder(Vec<String>);
+
+/// The entries of a plist dictionary, from which known keys are moved into typed fields.
+/// An entry is only taken if its value has the expected type, so anything else is kept as is.
+struct Entries(Vec<(String, Plist)>, KeyOrder);
+
+impl Entries {
+    fn new<'a>(plist: &Plist, err_msg: &'static str) -> Result<Entries, Error<'a>> {
+        match plist.as_dictionary() {
+            Some(entries) => Ok(Entries::from_slice(entries)),
+            None => Err(Error::BadData(err_msg)),
+        }
+    }
+
+    fn from_slice(entries: &[(String, Plist)]) -> Entries {
+        Entries(entries.to_vec(), KeyOrder(entries.iter().map(|e| e.0.clone()).collect()))
+    }
+
+    fn take<T, F: Fn(&Plist) -> Option<T>>(&mut self, key: &str, convert: F) -> Option<T> {
+        let position = self.0.iter().position(|e| e.0 == key && convert(&e.1).is_some())?;
+        convert(&self.0.remove(position).1)
+    }
+}
+
+/// Builds a plist dictionary from typed fields and the entries that were not understood.
+struct DictionaryBuilder(Vec<(String, Plist)>);
+
+impl DictionaryBuilder {
+    fn new() -> DictionaryBuilder {
+        DictionaryBuilder(Vec::new())
+    }
+
+    fn put<T, F: Fn(T) -> Plist>(&mut self, key: &str, value: Option<T>, convert: F) {
+        if let Some(value) = value {
+            self.0.push((key.to_string(), convert(value)));
+        }
+    }
+
+    /// Adds the `other` entries, except those with the key of a typed field, which replaces them.
+    /// Keys are written in `order`, followed by the typed fields and then the other entries it doesn't have.
+    fn finish(self, other: &[(String, Plist)], order: &KeyOrder) -> Plist {
+        let mut typed = self.0;
+        let mut other: Vec<(String, Plist)> = other.iter()
+            .filter(|e| typed.iter().all(|t| t.0 != e.0))
+            .cloned()
+            .collect();
+        let mut entries = Vec::with_capacity(typed.len() + other.len());
+        for key in &order.0 {
+            if let Some(position) = typed.iter().position(|e| &e.0 == key) {
+                entries.push(typed.remove(position));
+            } else if let Some(position) = other.iter().position(|e| &e.0 == key) {
+                entries.push(other.remove(position));
+            }
+        }
+        entries.extend(typed);
+        entries.extend(other);
+        Plist::Dictionary(entries)
+    }
+}
+
+/// A rectangle, written by Finder as `{{x, y}, {width, height}}`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Rect {
+    /// Parse the `{{x, y}, {width, height}}` form.
+    pub fn parse(s: &str) -> Option<Rect> {
+        let numbers: Vec<f64> = s.split(',')
+            .map(|part| part.trim_matches(|c: char| c == '{' || c == '}' || c.is_whitespace()).parse())
+            .collect::<Result<_, _>>()
+            .ok()?;
+        if numbers.len() != 4 || !s.trim().starts_with("{{") || !s.trim().ends_with("}}") {
+            return None;
+        }
+        Some(Rect {x: numbers[0], y: numbers[1], width: numbers[2], height: numbers[3]})
+    }
+}
+
+impl fmt::Display for Rect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{{{{}, {}}}, {{{}, {}}}}}", self.x, self.y, self.width, self.height)
+    }
+}
+
+/// The Finder window settings of a directory, stored in its "bwsp" record.
+#[derive(Debug, Clone, Default)]
+pub struct BrowserWindowSettings {
+    /// "WindowBounds", in screen coordinates.
+    pub window_bounds: Option<Rect>,
+    /// "SidebarWidth", in points.
+    pub sidebar_width: Option<i64>,
+    /// "ShowSidebar"
+    pub show_sidebar: Option<bool>,
+    /// "ShowToolbar"
+    pub show_toolbar: Option<bool>,
+    /// "ShowStatusBar"
+    pub show_status_bar: Option<bool>,
+    /// "ShowPathbar"
+    pub show_pathbar: Option<bool>,
+    /// "ShowTabView"
+    pub show_tab_view: Option<bool>,
+    /// Every other entry, kept as it was found.
+    pub other: Vec<(String, Plist)>,
+    /// Where each entry was, for `to_plist`. Not compared by `==`.
+    pub key_order: KeyOrder,
+}
+
+impl PartialEq for BrowserWindowSettings {
+    /// Compares every field but `key_order`.
+    fn eq(&self, rhs: &BrowserWindowSettings) -> bool {
+        let BrowserWindowSettings {window_bounds, sidebar_width, show_sidebar, show_toolbar, show_status_bar, show_pathbar, show_tab_view, other, key_order: _} = self;
+        *window_bounds == rhs.window_bounds &&
+            *sidebar_width == rhs.sidebar_width &&
+            *show_sidebar == rhs.show_sidebar &&
+            *show_toolbar == rhs.show_toolbar &&
+            *show_status_bar == rhs.show_status_bar &&
+            *show_pathbar == rhs.show_pathbar &&
+            *show_tab_view == rhs.show_tab_view &&
+            *other == rhs.other
+    }
+}
+
+impl BrowserWindowSettings {
+    pub fn from_plist<'a>(plist: &Plist) -> Result<BrowserWindowSettings, Error<'a>> {
+        let mut entries = Entries::new(plist, "\"bwsp\" must be a dictionary.")?;
+        Ok(BrowserWindowSettings {
+            window_bounds: entries.take("WindowBounds", |p| p.as_str().and_then(Rect::parse)),
+            sidebar_width: entries.take("SidebarWidth", Plist::as_integer),
+            show_sidebar: entries.take("ShowSidebar", Plist::as_bool),
+            show_toolbar: entries.take("ShowToolbar", Plist::as_bool),
+            show_status_bar: entries.take("ShowStatusBar", Plist::as_bool),
+            show_pathbar: entries.take("ShowPathbar", Plist::as_bool),
+            show_tab_view: entries.take("ShowTabView", Plist::as_bool),
+            other: entries.0,
+            key_order: entries.1,
+        })
+    }
+
+    /// Convert back into a plist, with the same entries the settings were read from.
+    pub fn to_plist(&self) -> Plist {
+        let mut dictionary = DictionaryBuilder::new();
+        dictionary.put("WindowBounds", self.window_bounds, |r| Plist::String(r.to_string()));
+        dictionary.put("SidebarWidth", self.sidebar_width, Plist::Integer);
+        dictionary.put("ShowSidebar", self.show_sidebar, Plist::Boolean);
+        dictionary.put("ShowToolbar", self.show_toolbar, Plist::Boolean);
+        dictionary.put("ShowStatusBar", self.show_status_bar, Plist::Boolean);
+        dictionary.put("ShowPathbar", self.show_pathbar, Plist::Boolean);
+        dictionary.put("ShowTabView", self.show_tab_view, Plist::Boolean);
+        dictionary.finish(&self.other, &self.key_order)
+    }
+}
+
+/// Only real numbers, so integers are kept as they are instead of being converted.
+fn real(plist: &Plist) -> Option<f64> {
+    match *plist {
+        Plist::Real(n) => Some(n),
+        _ => None,
+    }
+}
+
+/// How icons are arranged in icon view, the "arrangeBy" entry of "icvp".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Arrangement {
+    /// represented as "none"
+    None,
+    /// represented as "grid", snapped to the grid.
+    Grid,
+    /// represented as "name"
+    Name,
+    /// represented as "kind"
+    Kind,
+    /// represented as "dateModified"
+    DateModified,
+    /// represented as "dateCreated"
+    DateCreated,
+    /// represented as "dateAdded"
+    DateAdded,
+    /// represented as "dateLastOpened"
+    DateLastOpened,
+    /// represented as "size"
+    Size,
+    /// represented as "label"
+    Label,
+    /// Any other arrangement, as it is represented.
+    Other(String),
+}
+
+impl Arrangement {
+    pub fn from_name(name: &str) -> Arrangement {
+        match name {
+            "none" => Arrangement::None,
+            "grid" => Arrangement::Grid,
+            "name" => Arrangement::Name,
+            "kind" => Arrangement::Kind,
+            "dateModified" => Arrangement::DateModified,
+            "dateCreated" => Arrangement::DateCreated,
+            "dateAdded" => Arrangement::DateAdded,
+            "dateLastOpened" => Arrangement::DateLastOpened,
+            "size" => Arrangement::Size,
+            "label" => Arrangement::Label,
+            other => Arrangement::Other(other.to_string()),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match *self {
+            Arrangement::None => "none",
+            Arrangement::Grid => "grid",
+            Arrangement::Name => "name",
+            Arrangement::Kind => "kind",
+            Arrangement::DateModified => "dateModified",
+            Arrangement::DateCreated => "dateCreated",
+            Arrangement::DateAdded => "dateAdded",
+            Arrangement::DateLastOpened => "dateLastOpened",
+            Arrangement::Size => "size",
+            Arrangement::Label => "label",
+            Arrangement::Other(ref name) => name,
+        }
+    }
+}
+
+/// Where icon labels are placed, the "labelOnBottom" entry of "icvp".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LabelPosition {
+    Bottom,
+    Right,
+}
+
+/// The background of icon view, the "backgroundType" entry of "icvp".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IconViewBackground {
+    /// represented as 0
+    Default,
+    /// represented as 1, using the "backgroundColor*" entries.
+    Color,
+    /// represented as 2, using the "backgroundImageAlias" entry.
+    Picture,
+    /// Any other background type, as it is represented.
+    Other(i64),
+}
+
+impl IconViewBackground {
+    pub fn from_code(code: i64) -> IconViewBackground {
+        match code {
+            0 => IconViewBackground::Default,
+            1 => IconViewBackground::Color,
+            2 => IconViewBackground::Picture,
+            other => IconViewBackground::Other(other),
+        }
+    }
+
+    pub fn code(self) -> i64 {
+        match self {
+            IconViewBackground::Default => 0,
+            IconViewBackground::Color => 1,
+            IconViewBackground::Picture => 2,
+            IconViewBackground::Other(code) => code,
+        }
+    }
+}
+
+/// The icon view options of a directory, stored in its "icvp" record.
+#[derive(Debug, Clone, Default)]
+pub struct IconViewOptions {
+    /// "iconSize", in points.
+    pub icon_size: Option<f64>,
+    /// "textSize", in points.
+    pub text_size: Option<f64>,
+    /// "labelOnBottom"
+    pub label_position: Option<LabelPosition>,
+    /// "gridSpacing"
+    pub grid_spacing: Option<f64>,
+    /// "gridOffsetX"
+    pub grid_offset_x: Option<f64>,
+    /// "gridOffsetY"
+    pub grid_offset_y: Option<f64>,
+    /// "arrangeBy"
+    pub arrange_by: Option<Arrangement>,
+    /// "showIconPreview"
+    pub show_icon_preview: Option<bool>,
+    /// "showItemInfo"
+    pub show_item_info: Option<bool>,
+    /// "backgroundType"
+    pub background_type: Option<IconViewBackground>,
+    /// "backgroundColorRed", from 0 to 1.
+    pub background_color_red: Option<f64>,
+    /// "backgroundColorGreen", from 0 to 1.
+    pub background_color_green: Option<f64>,
+    /// "backgroundColorBlue", from 0 to 1.
+    pub background_color_blue: Option<f64>,
+    /// "backgroundImageAlias", an Alias record pointing to the background picture.
+    pub background_image_alias: Option<Vec<u8>>,
+    /// Every other entry, kept as it was found.
+    pub other: Vec<(String, Plist)>,
+    /// Where each entry was, for `to_plist`. Not compared by `==`.
+    pub key_order: KeyOrder,
+}
+
+impl PartialEq for IconViewOptions {
+    /// Compares every field but `key_order`.
+    fn eq(&self, rhs: &IconViewOptions) -> bool {
+        let IconViewOptions {icon_size, text_size, label_position, grid_spacing, grid_offset_x, grid_offset_y, arrange_by, show_icon_preview, show_item_info, background_type, background_color_red, background_color_green, background_color_blue, background_image_alias, other, key_order: _} = self;
+        *icon_size == rhs.icon_size &&
+            *text_size == rhs.text_size &&
+            *label_position == rhs.label_position &&
+            *grid_spacing == rhs.grid_spacing &&
+            *grid_offset_x == rhs.grid_offset_x &&
+            *grid_offset_y == rhs.grid_offset_y &&
+            *arrange_by == rhs.arrange_by &&
+            *show_icon_preview == rhs.show_icon_preview &&
+            *show_item_info == rhs.show_item_info &&
+            *background_type == rhs.background_type &&
+            *background_color_red == rhs.background_color_red &&
+            *background_color_green == rhs.background_color_green &&
+            *background_color_blue == rhs.background_color_blue &&
+            *background_image_alias == rhs.background_image_alias &&
+            *other == rhs.other
+    }
+}
+
+impl IconViewOptions {
+    pub fn from_plist<'a>(plist: &Plist) -> Result<IconViewOptions, Error<'a>> {
+        let mut entries = Entries::new(plist, "\"icvp\" must be a dictionary.")?;
+        Ok(IconViewOptions {
+            icon_size: entries.take("iconSize", real),
+            text_size: entries.take("textSize", real),
+            label_position: entries.take("labelOnBottom", |p| p.as_bool().map(|on_bottom| {
+                if on_bottom { LabelPosition::Bottom } else { LabelPosition::Right }
+            })),
+            grid_spacing: entries.take("gridSpacing", real),
+            grid_offset_x: entries.take("gridOffsetX", real),
+            grid_offset_y: entries.take("gridOffsetY", real),
+            arrange_by: entries.take("arrangeBy", |p| p.as_str().map(Arrangement::from_name)),
+            show_icon_preview: entries.take("showIconPreview", Plist::as_bool),
+            show_item_info: entries.take("showItemInfo", Plist::as_bool),
+            background_type: entries.take("backgroundType", |p| p.as_integer().map(IconViewBackground::from_code)),
+            background_color_red: entries.take("backgroundColorRed", real),
+            background_color_green: entries.take("backgroundColorGreen", real),
+            background_color_blue: entries.take("backgroundColorBlue", real),
+            background_image_alias: entries.take("backgroundImageAlias", |p| p.as_data().map(|d| d.to_vec())),
+            other: entries.0,
+            key_order: entries.1,
+        })
+    }
+
+    /// Convert back into a plist, with the same entries the options were read from.
+    pub fn to_plist(&self) -> Plist {
+        let mut dictionary = DictionaryBuilder::new();
+        dictionary.put("iconSize", self.icon_size, Plist::Real);
+        dictionary.put("textSize", self.text_size, Plist::Real);
+        dictionary.put("labelOnBottom", self.label_position, |p| Plist::Boolean(p == LabelPosition::Bottom));
+        dictionary.put("gridSpacing", self.grid_spacing, Plist::Real);
+        dictionary.put("gridOffsetX", self.grid_offset_x, Plist::Real);
+        dictionary.put("gridOffsetY", self.grid_offset_y, Plist::Real);
+        dictionary.put("arrangeBy", self.arrange_by.as_ref(), |a| Plist::String(a.name().to_string()));
+        dictionary.put("showIconPreview", self.show_icon_preview, Plist::Boolean);
+        dictionary.put("showItemInfo", self.show_item_info, Plist::Boolean);
+        dictionary.put("backgroundType", self.background_type, |b| Plist::Integer(b.code()));
+        dictionary.put("backgroundColorRed", self.background_color_red, Plist::Real);
+        dictionary.put("backgroundColorGreen", self.background_color_green, Plist::Real);
+        dictionary.put("backgroundColorBlue", self.background_color_blue, Plist::Real);
+        dictionary.put("backgroundImageAlias", self.background_image_alias.as_ref(), |d| Plist::Data(d.clone()));
+        dictionary.finish(&self.other, &self.key_order)
+    }
+
+    /// The equivalent of a legacy "icvo" record: its icon size, arrangement and label position.
+    pub fn from_legacy(options: &LegacyIconViewOptions) -> IconViewOptions {
+        IconViewOptions {
+            icon_size: Some(f64::from(options.icon_size())),
+            arrange_by: Some(Arrangement::from_name(&String::from_utf8_lossy(options.arrangement()))),
+            label_position: match options.label_position() {
+                Some(b"botm") => Some(LabelPosition::Bottom),
+                Some(b"rght") => Some(LabelPosition::Right),
+                _ => None,
+            },
+            ..IconViewOptions::default()
+        }
+    }
+}
+
+/// How the "columns" entry of list view settings is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnLayout {
+    /// A dictionary from column identifier to its settings, as in "lsvp".
+    Dictionary,
+    /// An array of column settings with an "identifier" entry, as in "lsvP".
+    Array,
+}
+
+/// The settings of one list view column.
+#[derive(Debug, Clone, Default)]
+pub struct Column {
+    /// The column shown, such as "name", "dateModified", "size", "kind" or "label".
+    pub identifier: String,
+    /// "width", in points.
+    pub width: Option<i64>,
+    /// "ascending"
+    pub ascending: Option<bool>,
+    /// "index", the position of the column.
+    pub index: Option<i64>,
+    /// "visible"
+    pub visible: Option<bool>,
+    /// Every other entry, kept as it was found.
+    pub other: Vec<(String, Plist)>,
+    /// Where each entry was, for `to_plist`. Not compared by `==`.
+    pub key_order: KeyOrder,
+}
+
+impl PartialEq for Column {
+    /// Compares every field but `key_order`.
+    fn eq(&self, rhs: &Column) -> bool {
+        let Column {identifier, width, ascending, index, visible, other, key_order: _} = self;
+        *identifier == rhs.identifier &&
+            *width == rhs.width &&
+            *ascending == rhs.ascending &&
+            *index == rhs.index &&
+            *visible == rhs.visible &&
+            *other == rhs.other
+    }
+}
+
+impl Column {
+    fn from_entries(identifier: String, mut entries: Entries) -> Column {
+        Column {
+            identifier,
+            width: entries.take("width", Plist::as_integer),
+            ascending: entries.take("ascending", Plist::as_bool),
+            index: entries.take("index", Plist::as_integer),
+            visible: entries.take("visible", Plist::as_bool),
+            other: entries.0,
+            key_order: entries.1,
+        }
+    }
+
+    fn to_plist(&self, layout: ColumnLayout) -> Plist {
+        let mut dictionary = DictionaryBuilder::new();
+        if layout == ColumnLayout::Array {
+            dictionary.put("identifier", Some(&self.identifier), |i| Plist::String(i.clone()));
+        }
+        dictionary.put("width", self.width, Plist::Integer);
+        dictionary.put("ascending", self.ascending, Plist::Boolean);
+        dictionary.put("index", self.index, Plist::Integer);
+        dictionary.put("visible", self.visible, Plist::Boolean);
+        dictionary.finish(&self.other, &self.key_order)
+    }
+}
+
+/// Reads the "columns" entry, in either layout.
+fn read_columns(plist: &Plist) -> Option<(ColumnLayout, Vec<Column>)> {
+    match *plist {
+        Plist::Dictionary(ref entries) => {
+            let columns = entries.iter()
+                .map(|e| Some(Column::from_entries(e.0.clone(), Entries::from_slice(e.1.as_dictionary()?))))
+                .collect::<Option<_>>()?;
+            Some((ColumnLayout::Dictionary, columns))
+        },
+        Plist::Array(ref values) => {
+            let columns = values.iter()
+                .map(|v| {
+                    let mut entries = Entries::from_slice(v.as_dictionary()?);
+                    let identifier = entries.take("identifier", |p| p.as_str().map(|s| s.to_string()))?;
+                    Some(Column::from_entries(identifier, entries))
+                })
+                .collect::<Option<_>>()?;
+            Some((ColumnLayout::Array, columns))
+        },
+        _ => None,
+    }
+}
+
+/// The list view settings of a directory, stored in its "lsvp" or "lsvP" record.
+#[derive(Debug, Clone, Default)]
+pub struct ListViewSettings {
+    /// "sortColumn", the identifier of the column sorted by.
+    pub sort_column: Option<String>,
+    /// "textSize", in points.
+    pub text_size: Option<f64>,
+    /// "iconSize", in points.
+    pub icon_size: Option<f64>,
+    /// "calculateAllSizes"
+    pub calculate_all_sizes: Option<bool>,
+    /// "useRelativeDates"
+    pub use_relative_dates: Option<bool>,
+    /// "columns", along with how they are stored.
+    pub columns: Option<(ColumnLayout, Vec<Column>)>,
+    /// Every other entry, kept as it was found.
+    pub other: Vec<(String, Plist)>,
+    /// Where each entry was, for `to_plist`. Not compared by `==`.
+    pub key_order: KeyOrder,
+}
+
+impl PartialEq for ListViewSettings {
+    /// Compares every field but `key_order`.
+    fn eq(&self, rhs: &ListViewSettings) -> bool {
+        let ListViewSettings {sort_column, text_size, icon_size, calculate_all_sizes, use_relative_dates, columns, other, key_order: _} = self;
+        *sort_column == rhs.sort_column &&
+            *text_size == rhs.text_size &&
+            *icon_size == rhs.icon_size &&
+            *calculate_all_sizes == rhs.calculate_all_sizes &&
+            *use_relative_dates == rhs.use_relative_dates &&
+            *columns == rhs.columns &&
+            *other == rhs.other
+    }
+}
+
+impl ListViewSettings {
+    pub fn from_plist<'a>(plist: &Plist) -> Result<ListViewSettings, Error<'a>> {
+        let mut entries = Entries::new(plist, "\"lsvp\"/\"lsvP\" must be a dictionary.")?;
+        Ok(ListViewSettings {
+            sort_column: entries.take("sortColumn", |p| p.as_str().map(|s| s.to_string())),
+            text_size: entries.take("textSize", real),
+            icon_size: entries.take("iconSize", real),
+            calculate_all_sizes: entries.take("calculateAllSizes", Plist::as_bool),
+            use_relative_dates: entries.take("useRelativeDates", Plist::as_bool),
+            columns: entries.take("columns", read_columns),
+            other: entries.0,
+            key_order: entries.1,
+        })
+    }
+
+    /// Convert back into a plist, with the same entries the settings were read from.
+    pub fn to_plist(&self) -> Plist {
+        let mut dictionary = DictionaryBuilder::new();
+        dictionary.put("sortColumn", self.sort_column.as_ref(), |s| Plist::String(s.clone()));
+        dictionary.put("textSize", self.text_size, Plist::Real);
+        dictionary.put("iconSize", self.icon_size, Plist::Real);
+        dictionary.put("calculateAllSizes", self.calculate_all_sizes, Plist::Boolean);
+        dictionary.put("useRelativeDates", self.use_relative_dates, Plist::Boolean);
+        dictionary.put("columns", self.columns.as_ref(), |&(layout, ref columns)| match layout {
+            ColumnLayout::Dictionary => Plist::Dictionary(columns.iter()
+                .map(|c| (c.identifier.clone(), c.to_plist(layout)))
+                .collect()),
+            ColumnLayout::Array => Plist::Array(columns.iter().map(|c| c.to_plist(layout)).collect()),
+        });
+        dictionary.finish(&self.other, &self.key_order)
+    }
+
+    /// The equivalent of a legacy "lsvt" record, the text size of list view in points.
+    pub fn from_text_size(text_size: i16) -> ListViewSettings {
+        ListViewSettings {
+            text_size: Some(f64::from(text_size)),
+            ..ListViewSettings::default()
+        }
+    }
+}
+
+/// What happened to one modern record when upgrading legacy view records.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LegacyUpgrade {
+    /// The directory the records belong to, usually ".".
+    pub file_name: String,
+    /// The legacy record the modern one was built from, "icvo", "fwi0" or "lsvt".
+    pub from: &'static str,
+    /// The modern record, "icvp", "vstl" or "lsvp".
+    pub to: &'static str,
+    pub outcome: UpgradeOutcome,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpgradeOutcome {
+    /// The modern record was missing and has been added.
+    Added,
+    /// A modern record already existed, so it was left as it was.
+    ModernRecordKept,
+}
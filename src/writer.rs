@@ -0,0 +1,768 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashSet};
+use std::ops::Range;
+use byteorder::{ByteOrder, BigEndian};
+use crate::Error;
+use crate::alias;
+use crate::hfs::compare_keys;
+use crate::plist;
+use crate::allocator::{Allocator, Directory, RecordLocation, RecordValue, GenericValue, BackgroundType, StyleType, Timestamp, LegacyIconViewOptions, WindowInfo};
+
+/// Size of a B-Tree node, also written as the page size in the DSDB root block.
+const PAGE_SIZE: u32 = 0x1000;
+
+/// A growable buffer, with methods for writing to it in _Big-Endian format_.
+/// The writing counterpart to `Block`.
+struct BlockWriter(Vec<u8>);
+
+impl BlockWriter {
+    fn new() -> BlockWriter {
+        BlockWriter(Vec::new())
+    }
+
+    fn write_bool(&mut self, value: bool) {
+        self.0.push(value as u8);
+    }
+
+    fn write_i16(&mut self, value: i16) {
+        let mut buf = [0; 2];
+        BigEndian::write_i16(&mut buf, value);
+        self.0.extend_from_slice(&buf);
+    }
+
+    fn write_u16(&mut self, value: u16) {
+        let mut buf = [0; 2];
+        BigEndian::write_u16(&mut buf, value);
+        self.0.extend_from_slice(&buf);
+    }
+
+    fn write_i32(&mut self, value: i32) {
+        let mut buf = [0; 4];
+        BigEndian::write_i32(&mut buf, value);
+        self.0.extend_from_slice(&buf);
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        let mut buf = [0; 4];
+        BigEndian::write_u32(&mut buf, value);
+        self.0.extend_from_slice(&buf);
+    }
+
+    fn write_i64(&mut self, value: i64) {
+        let mut buf = [0; 8];
+        BigEndian::write_i64(&mut buf, value);
+        self.0.extend_from_slice(&buf);
+    }
+
+    fn write_buf(&mut self, data: &[u8]) {
+        self.0.extend_from_slice(data);
+    }
+
+    /// Writes a 4-byte `length` and then the (`length*2`)-byte UTF-16 form of `s`.
+    fn write_utf16(&mut self, s: &str) {
+        let units: Vec<u16> = s.encode_utf16().collect();
+        self.write_u32(units.len() as u32);
+        for unit in units {
+            self.write_u16(unit);
+        }
+    }
+
+    // Writes a 4-byte length, then the data.
+    fn write_blob(&mut self, data: &[u8]) {
+        self.write_u32(data.len() as u32);
+        self.write_buf(data);
+    }
+
+    fn write_record<'a>(&mut self, file_name: &str, structure_type: &'a str, value: &RecordValue<'a>) -> Result<(), Error<'a>> {
+        self.write_utf16(file_name);
+        self.write_record_info(structure_type, value)
+    }
+
+    fn write_date_time(&mut self, date_time: Timestamp) {
+        self.write_i64(date_time.ticks());
+    }
+
+    fn write_bool_value<'a>(&mut self, value: &RecordValue<'a>, err_msg: &'static str) -> Result<(), Error<'a>> {
+        match *value {
+            RecordValue::Bool(b) => {
+                self.write_buf(b"bool");
+                self.write_bool(b);
+                Ok(())
+            },
+            _ => Err(Error::BadData(err_msg)),
+        }
+    }
+
+    fn write_long_value<'a>(&mut self, value: &RecordValue<'a>, err_msg: &'static str) -> Result<(), Error<'a>> {
+        match *value {
+            RecordValue::I32(n) => {
+                self.write_buf(b"long");
+                self.write_i32(n);
+                Ok(())
+            },
+            _ => Err(Error::BadData(err_msg)),
+        }
+    }
+
+    fn write_shor_value<'a>(&mut self, value: &RecordValue<'a>, err_msg: &'static str) -> Result<(), Error<'a>> {
+        match *value {
+            RecordValue::I16(n) => {
+                self.write_buf(b"shor");
+                self.write_buf(&[0, 0]); // shor is 4 bytes long, but only 16 bit.
+                self.write_i16(n);
+                Ok(())
+            },
+            _ => Err(Error::BadData(err_msg)),
+        }
+    }
+
+    fn write_comp_value<'a>(&mut self, value: &RecordValue<'a>, err_msg: &'static str) -> Result<(), Error<'a>> {
+        match *value {
+            RecordValue::I64(n) => {
+                self.write_buf(b"comp");
+                self.write_i64(n);
+                Ok(())
+            },
+            _ => Err(Error::BadData(err_msg)),
+        }
+    }
+
+    fn write_ustr_value<'a>(&mut self, value: &RecordValue<'a>, err_msg: &'static str) -> Result<(), Error<'a>> {
+        match *value {
+            RecordValue::String(ref s) => {
+                self.write_buf(b"ustr");
+                self.write_utf16(s);
+                Ok(())
+            },
+            _ => Err(Error::BadData(err_msg)),
+        }
+    }
+
+    /// The writing counterpart to `Block::read_window_info`.
+    fn write_window_info(&mut self, info: &WindowInfo) {
+        self.write_i16(info.top);
+        self.write_i16(info.left);
+        self.write_i16(info.bottom);
+        self.write_i16(info.right);
+        self.write_buf(&info.view_style);
+        self.write_buf(&info.unknown);
+    }
+
+    /// Writes a blob record value. If `lengths` is not empty, the blob must be one of those lengths.
+    fn write_blob_value<'a>(&mut self, value: &RecordValue<'a>, lengths: &[usize], err_msg: &'static str) -> Result<(), Error<'a>> {
+        match *value {
+            RecordValue::Slice(blob) if lengths.is_empty() || lengths.contains(&blob.len()) => {
+                self.write_buf(b"blob");
+                self.write_blob(blob);
+                Ok(())
+            },
+            _ => Err(Error::BadData(err_msg)),
+        }
+    }
+
+    fn write_plist_value<'a>(&mut self, value: &RecordValue<'a>, err_msg: &'static str) -> Result<(), Error<'a>> {
+        match *value {
+            RecordValue::Plist(ref record) => {
+                self.write_buf(b"blob");
+                match record.source() {
+                    Some(source) => self.write_blob(source),
+                    None => self.write_blob(&plist::write(record.value())),
+                }
+                Ok(())
+            },
+            _ => self.write_blob_value(value, &[], err_msg),
+        }
+    }
+
+    /// The writing counterpart to `Block::read_generic_value`.
+    fn write_generic_value(&mut self, value: &GenericValue) {
+        match *value {
+            GenericValue::Long(n) => {
+                self.write_buf(b"long");
+                self.write_i32(n);
+            },
+            GenericValue::Short(n) => {
+                self.write_buf(b"shor");
+                self.write_buf(&[0, 0]);
+                self.write_i16(n);
+            },
+            GenericValue::Bool(b) => {
+                self.write_buf(b"bool");
+                self.write_bool(b);
+            },
+            GenericValue::Blob(blob) => {
+                self.write_buf(b"blob");
+                self.write_blob(blob);
+            },
+            GenericValue::Type(four_char_code) => {
+                self.write_buf(b"type");
+                self.write_buf(four_char_code);
+            },
+            GenericValue::UStr(ref s) => {
+                self.write_buf(b"ustr");
+                self.write_utf16(s);
+            },
+            GenericValue::Comp(n) => {
+                self.write_buf(b"comp");
+                self.write_i64(n);
+            },
+            GenericValue::DateTime(date_time) => {
+                self.write_buf(b"dutc");
+                self.write_date_time(date_time);
+            },
+        }
+    }
+
+    /// The writing counterpart to `Block::read_record_info`,
+    /// rejecting values that `read_record_info` would never produce for the given code.
+    fn write_record_info<'a>(&mut self, structure_type: &'a str, value: &RecordValue<'a>) -> Result<(), Error<'a>> {
+        let code = structure_type.as_bytes();
+        if code.len() != 4 {
+            return Err(Error::UnkonwnStructureType(code));
+        }
+        self.write_buf(code);
+        match code {
+            b"BKGD" => {
+                self.write_buf(b"blob");
+                self.write_u32(12);
+                match *value {
+                    RecordValue::Background(BackgroundType::Default) => {
+                        self.write_buf(b"DefB");
+                        self.write_buf(&[0; 8]);
+                    },
+                    RecordValue::Background(BackgroundType::SolidColor(r, g, b)) => {
+                        self.write_buf(b"ClrB");
+                        self.write_u16(r);
+                        self.write_u16(g);
+                        self.write_u16(b);
+                        self.write_buf(&[0; 2]);
+                    },
+                    RecordValue::Background(BackgroundType::Picture(picture_property_blob_length, _))
+                    | RecordValue::U32(picture_property_blob_length) => {
+                        self.write_buf(b"PctB");
+                        self.write_u32(picture_property_blob_length);
+                        self.write_buf(&[0; 4]);
+                    },
+                    _ => return Err(Error::BadData("\"BKGD\" only takes blobs describing the background.")),
+                }
+                Ok(())
+            },
+            b"ICVO" => self.write_bool_value(value, "\"ICVO\" only takes bool"),
+            b"Iloc" => match *value {
+                RecordValue::IconLocation(ref location) => {
+                    self.write_buf(b"blob");
+                    self.write_u32(16);
+                    self.write_u32(location.x);
+                    self.write_u32(location.y);
+                    self.write_buf(&location.unknown);
+                    Ok(())
+                },
+                _ => Err(Error::BadData("\"Iloc\" only takes a 16-byte blob.")),
+            },
+            b"LSVO" => self.write_bool_value(value, "\"LSVO\" only takes bool"),
+            b"bwsp" => self.write_plist_value(value, "\"bwsp\" only takes a plist blob"),
+            b"cmmt" => self.write_ustr_value(value, "\"cmmt\" only takes ustr"),
+            b"dilc" => match *value {
+                RecordValue::DesktopIconLocation(ref location) => {
+                    self.write_buf(b"blob");
+                    self.write_u32(32);
+                    self.write_u32(location.flags);
+                    self.write_buf(&location.unknown);
+                    self.write_i32(location.x);
+                    self.write_i32(location.y);
+                    self.write_buf(&location.trailing);
+                    Ok(())
+                },
+                _ => Err(Error::BadData("\"dilc\" only takes a 32-byte blob.")),
+            },
+            b"dscl" => self.write_bool_value(value, "\"dscl\" only takes bool"),
+            b"extn" => self.write_ustr_value(value, "\"extn\" only takes ustr"),
+            b"fwi0" => match *value {
+                RecordValue::WindowInfo(ref info) => {
+                    self.write_buf(b"blob");
+                    self.write_u32(16);
+                    self.write_window_info(info);
+                    Ok(())
+                },
+                _ => Err(Error::BadData("\"fwi0\" only takes 16-byte blob")),
+            },
+            b"fwsw" => self.write_long_value(value, "\"fwsw\" only takes long"),
+            b"fwvh" => self.write_shor_value(value, "\"fwvh\" only takes shor"),
+            b"GRP0" => self.write_ustr_value(value, "\"GRP0\" only takes ustr"),
+            b"icgo" => self.write_blob_value(value, &[8], "\"icgo\" only takes 8-byte blob"),
+            b"icsp" => self.write_blob_value(value, &[8], "\"icsp\" only takes 8-byte blob"),
+            b"icvo" => match *value {
+                RecordValue::LegacyIconView(LegacyIconViewOptions::Icvo {ref unknown, icon_size, ref arrangement}) => {
+                    self.write_buf(b"blob");
+                    self.write_u32(18);
+                    self.write_buf(b"icvo");
+                    self.write_buf(unknown);
+                    self.write_u16(icon_size);
+                    self.write_buf(arrangement);
+                    Ok(())
+                },
+                RecordValue::LegacyIconView(LegacyIconViewOptions::Icv4 {icon_size, ref arrangement, ref label_position, ref unknown}) => {
+                    self.write_buf(b"blob");
+                    self.write_u32(26);
+                    self.write_buf(b"icv4");
+                    self.write_u16(icon_size);
+                    self.write_buf(arrangement);
+                    self.write_buf(label_position);
+                    self.write_buf(unknown);
+                    Ok(())
+                },
+                _ => Err(Error::BadData("\"icvo\" only takes 18 or 26 byte blob.")),
+            },
+            b"icvp" => self.write_plist_value(value, "\"icvp\" only takes a plist blob"),
+            b"icvt" => self.write_shor_value(value, "\"icvt\" only takes shor"),
+            b"info" => match *value {
+                RecordValue::FinderInfo(ref info) => {
+                    self.write_buf(b"blob");
+                    self.write_u32(if info.extra.is_some() { 48 } else { 40 });
+                    self.write_window_info(&info.window);
+                    self.write_buf(&info.unknown);
+                    if let Some(ref extra) = info.extra {
+                        self.write_buf(extra);
+                    }
+                    Ok(())
+                },
+                _ => Err(Error::BadData("\"info\" only takes 40 or 48 byte blob.")),
+            },
+            b"logS" | b"lg1S" => self.write_comp_value(value, "\"logS\"/\"lg1S\" only takes comp"),
+            b"lssp" => self.write_blob_value(value, &[8], "\"lssp\" only takes 8-byte blob"),
+            b"lsvo" => self.write_blob_value(value, &[76], "\"lsvo\" only takes 76-byte blob"),
+            b"lsvt" => self.write_shor_value(value, "\"lsvt\" only take shor"),
+            b"lsvp" => self.write_plist_value(value, "\"lsvp\" only takes a plist blob"),
+            b"lsvP" => self.write_plist_value(value, "\"lsvP\" only takes a plist blob"),
+            b"modD" | b"moDD" => match *value {
+                RecordValue::DateTime(date_time) => {
+                    self.write_buf(b"dutc");
+                    self.write_date_time(date_time);
+                    Ok(())
+                },
+                _ => Err(Error::BadData("\"modD\"/\"moDD\" only takes dutc")),
+            },
+            b"phyS" | b"ph1S" => self.write_comp_value(value, "\"phyS\"/\"ph1S\" only takes comp"),
+            b"pBBk" | b"pBB0" => match *value {
+                RecordValue::Bookmark(ref record) => {
+                    self.write_buf(b"blob");
+                    self.write_blob(record.source());
+                    Ok(())
+                },
+                _ => self.write_blob_value(value, &[], "\"pBBk\" and \"pBB0\" only take blob"),
+            },
+            b"pict" => match *value {
+                RecordValue::Alias(ref record) => {
+                    self.write_buf(b"blob");
+                    match record.source() {
+                        Some(source) => self.write_blob(source),
+                        None => self.write_blob(&alias::write(record.value())),
+                    }
+                    Ok(())
+                },
+                _ => self.write_blob_value(value, &[], "\"pict\" only takes blob"),
+            },
+            b"vSrn" => self.write_long_value(value, "\"vSrn\" only takes long"),
+            b"vstl" => match *value {
+                RecordValue::Style(ref style) => {
+                    self.write_buf(b"type");
+                    self.write_buf(match *style {
+                        StyleType::Icon => b"icnv",
+                        StyleType::ColumnBrowser => b"clmv",
+                        StyleType::List => b"Nlsv",
+                        StyleType::CoverFlow => b"Flwv",
+                    });
+                    Ok(())
+                },
+                _ => Err(Error::BadData("\"vstl\" only takes type")),
+            },
+            b"ptbL" => self.write_ustr_value(value, "\"ptbL\" only takes ustr"),
+            b"ptbN" => self.write_ustr_value(value, "\"ptbN\" only takes ustr"),
+            _ => match *value {
+                RecordValue::Unknown(code, ref generic) if code == structure_type => {
+                    if let GenericValue::Type(four_char_code) = *generic {
+                        if four_char_code.len() != 4 {
+                            return Err(Error::BadData("\"type\" values must be four bytes long."));
+                        }
+                    }
+                    self.write_generic_value(generic);
+                    Ok(())
+                },
+                _ => Err(Error::UnkonwnStructureType(code)),
+            },
+        }
+    }
+}
+
+/// Checks that `value` is a valid value for records of type `structure_type`.
+pub fn check_record<'a>(structure_type: &'a str, value: &RecordValue<'a>) -> Result<(), Error<'a>> {
+    BlockWriter::new().write_record_info(structure_type, value)
+}
+
+/// A buddy-allocator over the 2^31 byte address space of the file,
+/// handing out blocks the same way the `Allocator` expects to find them.
+struct BuddyAllocator {
+    /// Addresses of the allocated blocks, in the format `Allocator::offsets` reads.
+    offsets: Vec<u32>,
+    /// Offsets of free blocks, indexed by the log2 of their size.
+    free_list: Vec<Vec<u32>>,
+    /// One past the last byte of the last allocated block.
+    end: u32,
+}
+
+impl BuddyAllocator {
+    fn new() -> BuddyAllocator {
+        let mut free_list = vec![Vec::new(); 32];
+        free_list[31].push(0);
+        BuddyAllocator {offsets: Vec::new(), free_list, end: 0}
+    }
+
+    /// Allocates space for `size` bytes, returning the block address (offset | log2(size)).
+    fn allocate<'a>(&mut self, size: u32) -> Result<u32, Error<'a>> {
+        // Blocks are never smaller than 32 bytes.
+        let width = std::cmp::max(5, 32 - size.saturating_sub(1).leading_zeros()) as usize;
+        let mut current = width;
+        while current < 32 && self.free_list[current].is_empty() {
+            current += 1;
+        }
+        if current >= 32 {
+            return Err(Error::BadData("Not enough space left in the buddy-allocator."));
+        }
+        let offset = self.free_list[current].remove(0);
+        // Split the block in halves until it is the right size, freeing the upper halves.
+        while current > width {
+            current -= 1;
+            let buddy = offset + (1 << current);
+            let list = &mut self.free_list[current];
+            let position = list.binary_search(&buddy).unwrap_or_else(|p| p);
+            list.insert(position, buddy);
+        }
+        self.end = std::cmp::max(self.end, offset + (1 << width));
+        Ok(offset | width as u32)
+    }
+
+    /// Allocates a block of `size` bytes, returning its block ID.
+    fn allocate_block<'a>(&mut self, size: u32) -> Result<u32, Error<'a>> {
+        let address = self.allocate(size)?;
+        self.offsets.push(address);
+        Ok(self.offsets.len() as u32 - 1)
+    }
+}
+
+/// Splits one level of the B-Tree into nodes that fit in a page.
+/// Each node covers a range of `entries`, and the entry after each range
+/// (except the last) is promoted to the level above.
+/// Entries of internal nodes are preceded by a 4-byte child pointer.
+fn split_level<'a>(entries: &[Vec<u8>], internal: bool) -> Result<Vec<Range<usize>>, Error<'a>> {
+    let entry_size = |i: usize| entries[i].len() as u32 + if internal { 4 } else { 0 };
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    loop {
+        // 8 bytes for the rightmost child pointer and the record count.
+        let mut used = 8;
+        let mut end = start;
+        while end < entries.len() && used + entry_size(end) <= PAGE_SIZE {
+            used += entry_size(end);
+            end += 1;
+        }
+        if end == entries.len() {
+            ranges.push(start..end);
+            return Ok(ranges);
+        }
+        if end == start {
+            return Err(Error::BadData("Record is too large to fit in a B-Tree node."));
+        }
+        if end + 1 == entries.len() {
+            // Promoting the last entry would leave an empty node behind it,
+            // so promote the one before it instead.
+            end -= 1;
+            if end == start {
+                // Only one entry fits in this node, which would leave it empty instead.
+                // Take the last entry of the nearest node that can spare one,
+                // moving the nodes in between, which hold one entry each, back by one.
+                let donor = ranges.iter().rposition(|range: &Range<usize>| range.len() > 1)
+                    .ok_or(Error::BadData("Records are too large to be split into B-Tree nodes."))?;
+                ranges[donor].end -= 1;
+                for range in &mut ranges[donor + 1..] {
+                    range.start -= 1;
+                    range.end -= 1;
+                }
+                start -= 1;
+            }
+        }
+        ranges.push(start..end);
+        start = end + 1;
+    }
+}
+
+/// A B-Tree node, serialized and waiting to be placed in its block.
+struct Node {
+    block_id: u32,
+    data: Vec<u8>,
+}
+
+/// The shape of a freshly built B-Tree, as recorded by the DSDB root block.
+struct Tree {
+    root: u32,
+    num_internals: u32,
+    num_records: u32,
+    nodes: Vec<Node>,
+}
+
+/// Packs the sorted, serialized `records` into B-Tree nodes, bottom up.
+fn build_tree<'a>(allocator: &mut BuddyAllocator, records: Vec<Vec<u8>>) -> Result<Tree, Error<'a>> {
+    let num_records = records.len() as u32;
+    let mut nodes = Vec::new();
+    let mut entries = records;
+    let mut children: Option<Vec<u32>> = None;
+    let mut num_internals = 0;
+    loop {
+        let ranges = split_level(&entries, children.is_some())?;
+        let mut level_ids = Vec::with_capacity(ranges.len());
+        for range in &ranges {
+            let mut node = BlockWriter::new();
+            // Leaves have no rightmost child pointer, which is written as 0.
+            node.write_u32(children.as_ref().map_or(0, |c| c[range.end]));
+            node.write_u32(range.len() as u32);
+            for i in range.clone() {
+                if let Some(ref c) = children {
+                    node.write_u32(c[i]);
+                }
+                node.write_buf(&entries[i]);
+            }
+            let block_id = allocator.allocate_block(PAGE_SIZE)?;
+            nodes.push(Node {block_id, data: node.0});
+            level_ids.push(block_id);
+        }
+        if level_ids.len() == 1 {
+            return Ok(Tree {root: level_ids[0], num_internals, num_records, nodes});
+        }
+        // Every range but the last promotes the entry right after it.
+        entries = ranges[..ranges.len() - 1].iter()
+            .map(|range| entries[range.end].clone())
+            .collect();
+        children = Some(level_ids);
+        num_internals += 1;
+    }
+}
+
+/// The records in `contents`, in the order they are stored in the B-Tree.
+pub fn sorted_records<'a, 'b>(contents: &'b BTreeMap<String, BTreeMap<&'a str, RecordValue<'a>>>) -> Vec<(&'b str, &'a str, &'b RecordValue<'a>)> {
+    let mut records: Vec<(&str, &'a str, &RecordValue<'a>)> = contents.iter()
+        .flat_map(|(file_name, metadata)| {
+            metadata.iter().map(move |(code, value)| (file_name.as_str(), *code, value))
+        })
+        .collect();
+    records.sort_by(|l, r| compare_keys((l.0, l.1), (r.0, r.1)));
+    records
+}
+
+/// Serializes the records in `contents` into the bytes of a complete `.DS_Store` file.
+pub fn write<'a>(contents: &BTreeMap<String, BTreeMap<&'a str, RecordValue<'a>>>) -> Result<Vec<u8>, Error<'a>> {
+    let records = sorted_records(contents);
+    // Names that only differ in case would give two records the same key.
+    if records.windows(2).any(|pair| compare_keys((pair[0].0, pair[0].1), (pair[1].0, pair[1].1)) == Ordering::Equal) {
+        return Err(Error::BadData("Two records have the same filename and code, as the B-Tree compares them."));
+    }
+    let mut encoded = Vec::with_capacity(records.len());
+    for (file_name, structure_type, value) in records {
+        let mut record = BlockWriter::new();
+        record.write_record(file_name, structure_type, value)?;
+        encoded.push(record.0);
+    }
+
+    let mut allocator = BuddyAllocator::new();
+    // The prelude always sits at the very start of the file.
+    allocator.allocate(32)?;
+    // Block 0 is the info block, allocated last, once its size is known.
+    allocator.offsets.push(0);
+    let dsdb_location = allocator.allocate_block(32)?;
+    let tree = build_tree(&mut allocator, encoded)?;
+
+    // Allocating the info block can split at most one free block per size.
+    let padded_offsets = ((allocator.offsets.len() + 255) / 256) * 256;
+    let free_entries: usize = allocator.free_list.iter().map(|l| l.len()).sum();
+    let info_size = 8 + 4 * padded_offsets + 13 + 4 * 32 + 4 * (free_entries + 31);
+    let info_address = allocator.allocate(info_size as u32)?;
+    allocator.offsets[0] = info_address;
+
+    let mut info = BlockWriter::new();
+    info.write_u32(allocator.offsets.len() as u32);
+    info.write_u32(0);
+    for &offset in &allocator.offsets {
+        info.write_u32(offset);
+    }
+    info.write_buf(&vec![0; 4 * (padded_offsets - allocator.offsets.len())]);
+    // The table of contents, with its only entry, "DSDB".
+    info.write_u32(1);
+    info.write_buf(&[4]);
+    info.write_buf(b"DSDB");
+    info.write_u32(dsdb_location);
+    for list in &allocator.free_list {
+        info.write_u32(list.len() as u32);
+        for &offset in list {
+            info.write_u32(offset);
+        }
+    }
+
+    let mut dsdb = BlockWriter::new();
+    dsdb.write_u32(tree.root);
+    dsdb.write_u32(tree.num_internals);
+    dsdb.write_u32(tree.num_records);
+    dsdb.write_u32(tree.nodes.len() as u32);
+    dsdb.write_u32(PAGE_SIZE);
+
+    let info_offset = info_address & !0x1f;
+    let info_block_size = 1 << (info_address & 0x1f);
+    let mut prelude = BlockWriter::new();
+    prelude.write_buf(b"Bud1");
+    prelude.write_u32(info_offset);
+    prelude.write_u32(info_block_size);
+    prelude.write_u32(info_offset);
+    prelude.write_buf(&[0; 16]);
+
+    let mut file = vec![0; 4 + allocator.end as usize];
+    file[..4].copy_from_slice(&[0, 0, 0, 1]);
+    place_at(&mut file, 0, 32, &prelude.0)?;
+    place(&mut file, info_address, &info.0)?;
+    place(&mut file, allocator.offsets[dsdb_location as usize], &dsdb.0)?;
+    for node in &tree.nodes {
+        place(&mut file, allocator.offsets[node.block_id as usize], &node.data)?;
+    }
+    Ok(file)
+}
+
+/// Copies `data` into the block at `address` (offset | log2(size)) of `file`.
+fn place<'a>(file: &mut [u8], address: u32, data: &[u8]) -> Result<(), Error<'a>> {
+    place_at(file, address & !0x1f, 1 << (address & 0x1f), data)
+}
+
+/// Copies `data` into the `size`-byte block at `offset` of `file`.
+fn place_at<'a>(file: &mut [u8], offset: u32, size: u32, data: &[u8]) -> Result<(), Error<'a>> {
+    if data.len() > size as usize {
+        return Err(Error::BadData("Data does not fit in its block."));
+    }
+    // Every block is offset by the 4-byte header.
+    let start = 4 + offset as usize;
+    if file.len() < start + data.len() {
+        return Err(Error::NotEnoughData);
+    }
+    file[start..start + data.len()].copy_from_slice(data);
+    Ok(())
+}
+
+/// Serializes `directory` back into the exact layout `allocator` read it from,
+/// so an unmodified store gives back the bytes it was parsed from.
+/// Values may have changed, but records may not have been added or removed,
+/// and every node must still fit in its block.
+pub fn write_lossless<'a>(allocator: &Allocator<'a>, directory: &Directory<'a>) -> Result<Vec<u8>, Error<'a>> {
+    let laid_out: HashSet<(&str, &str)> = directory.nodes.iter()
+        .flat_map(|node| node.records.iter().map(|&(_, ref file_name, code, _)| (file_name.as_str(), code)))
+        .collect();
+    let num_records: usize = directory.contents.values().map(|metadata| metadata.len()).sum();
+    let unchanged = laid_out.len() == num_records && laid_out.iter().all(|&(file_name, code)| {
+        directory.contents.get(file_name).map_or(false, |metadata| metadata.contains_key(code))
+    });
+    if !unchanged {
+        return Err(Error::BadData("Records were added or removed since the store was read."));
+    }
+
+    // Bytes outside of what is rewritten here (unused space, padding) are kept as they were.
+    let mut file = allocator.data().to_vec();
+
+    let mut prelude = BlockWriter::new();
+    prelude.write_buf(b"Bud1");
+    prelude.write_u32(allocator.info_block_offset);
+    prelude.write_u32(allocator.info_block_size);
+    prelude.write_u32(allocator.info_block_offset);
+    prelude.write_buf(allocator.prelude_unknown);
+    place_at(&mut file, 0, 32, &prelude.0)?;
+
+    let mut offsets = BlockWriter::new();
+    offsets.write_u32(allocator.offsets.len() as u32);
+    offsets.write_u32(allocator.offsets_unknown);
+    for &offset in &allocator.offsets {
+        offsets.write_u32(offset);
+    }
+    place_at(&mut file, allocator.info_block_offset, allocator.info_block_size, &offsets.0)?;
+
+    let mut toc = BlockWriter::new();
+    toc.write_u32(1);
+    toc.write_buf(&[4]);
+    toc.write_buf(b"DSDB");
+    toc.write_u32(allocator.dsdb_location);
+    for list in &allocator.free_list {
+        toc.write_u32(list.len() as u32);
+        for &offset in list {
+            toc.write_u32(offset);
+        }
+    }
+    // The table of contents follows the offsets, padded to a multiple of 256 entries.
+    let padding = 8 + 4 * ((allocator.offsets.len() + 255) / 256) as u32 * 256;
+    if padding > allocator.info_block_size {
+        return Err(Error::NotEnoughData);
+    }
+    place_at(&mut file, allocator.info_block_offset + padding, allocator.info_block_size - padding, &toc.0)?;
+
+    let mut dsdb = BlockWriter::new();
+    dsdb.write_u32(directory.root_node);
+    dsdb.write_u32(directory.num_internals);
+    dsdb.write_u32(directory.num_records);
+    dsdb.write_u32(directory.num_nodes);
+    dsdb.write_u32(PAGE_SIZE);
+    place(&mut file, block_address(allocator, allocator.dsdb_location)?, &dsdb.0)?;
+
+    // Later occurrences of duplicate records are written back as they were read, the first one from `contents`.
+    let repeated: BTreeMap<RecordLocation, &RecordValue<'a>> = directory.duplicates.iter()
+        .flat_map(|duplicate| duplicate.occurrences.iter().skip(1).map(|(location, value)| (*location, value)))
+        .collect();
+    for node in &directory.nodes {
+        let mut data = BlockWriter::new();
+        data.write_u32(node.rightmost_child);
+        data.write_u32(node.records.len() as u32);
+        for (index, &(child, ref file_name, structure_type, source)) in node.records.iter().enumerate() {
+            if node.rightmost_child != 0 {
+                data.write_u32(child);
+            }
+            let location = RecordLocation {block_id: node.block_id, index};
+            let value = match repeated.get(&location) {
+                Some(value) => value,
+                None => &directory.contents[file_name][structure_type],
+            };
+            let mut info = BlockWriter::new();
+            info.write_record_info(structure_type, value)?;
+            data.write_utf16(file_name);
+            // Unchanged values keep the bytes that were discarded when reading them.
+            data.write_buf(if same_value(&info.0, source) { source } else { &info.0 });
+        }
+        place(&mut file, block_address(allocator, node.block_id)?, &data.0)?;
+    }
+    Ok(file)
+}
+
+/// Whether `encoded` and `source`, both the record type, data type and value of a record,
+/// only differ in bytes that reading the record discards: the padding of "BKGD",
+/// the upper half of a "shor" and "bool" bytes other than 0 and 1.
+fn same_value(encoded: &[u8], source: &[u8]) -> bool {
+    if encoded.len() != source.len() || encoded.len() < 8 || encoded[..8] != source[..8] {
+        return false;
+    }
+    match (&encoded[..4], &encoded[4..8]) {
+        (_, b"shor") => encoded[10..] == source[10..],
+        (_, b"bool") => (encoded[8] == 1) == (source[8] == 1),
+        // A 12-byte blob: the kind of background, then its value and padding.
+        (b"BKGD", b"blob") if encoded.len() == 24 => encoded[8..16] == source[8..16] && match &encoded[12..16] {
+            b"ClrB" => encoded[16..22] == source[16..22],
+            b"PctB" => encoded[16..20] == source[16..20],
+            _ => true,
+        },
+        _ => encoded == source,
+    }
+}
+
+fn block_address<'a>(allocator: &Allocator<'a>, block_id: u32) -> Result<u32, Error<'a>> {
+    match allocator.offsets.get(block_id as usize) {
+        Some(&address) => Ok(address),
+        None => Err(Error::BlockDoesntExist),
+    }
+}
@@ -0,0 +1,267 @@
+extern crate ds_store;
+
+use std::collections::HashMap;
+use ds_store::{Alias, DsStore, IconViewBackground, RecordValue, Timestamp};
+use ds_store::alias::{self, AliasExtra, AliasKind};
+use ds_store::allocator::BackgroundType;
+
+fn push_u16(data: &mut Vec<u8>, value: u16) {
+    data.extend_from_slice(&value.to_be_bytes());
+}
+
+fn push_u32(data: &mut Vec<u8>, value: u32) {
+    data.extend_from_slice(&value.to_be_bytes());
+}
+
+/// A length byte and `s`, padded with zeros to `size` bytes.
+fn push_pascal(data: &mut Vec<u8>, s: &[u8], size: usize) {
+    data.push(s.len() as u8);
+    data.extend_from_slice(s);
+    data.resize(data.len() + size - 1 - s.len(), 0);
+}
+
+fn push_extra(data: &mut Vec<u8>, tag: i16, value: &[u8]) {
+    push_u16(data, tag as u16);
+    push_u16(data, value.len() as u16);
+    data.extend_from_slice(value);
+    if value.len() % 2 == 1 {
+        data.push(0);
+    }
+}
+
+fn unicode_name(s: &str) -> Vec<u8> {
+    let units: Vec<u16> = s.encode_utf16().collect();
+    let mut data = Vec::new();
+    push_u16(&mut data, units.len() as u16);
+    for unit in units {
+        push_u16(&mut data, unit);
+    }
+    data
+}
+
+/// Sets the record size, once all of `body` is assembled.
+fn finish(mut body: Vec<u8>) -> Vec<u8> {
+    push_u16(&mut body, 0xffff);
+    push_u16(&mut body, 0);
+    let size = body.len() as u16;
+    body[4..6].copy_from_slice(&size.to_be_bytes());
+    body
+}
+
+/// A version 2 alias to "/Volumes/Installer/.background/bg.png", like Finder makes for DMG backgrounds.
+fn version_2() -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(&[0; 4]);
+    push_u16(&mut data, 0);
+    push_u16(&mut data, 2);
+    push_u16(&mut data, 0); // a file
+    push_pascal(&mut data, b"Installer", 28);
+    push_u32(&mut data, 3_600_000_000);
+    data.extend_from_slice(b"H+");
+    push_u16(&mut data, 5);
+    push_u32(&mut data, 18);
+    push_pascal(&mut data, b"bg.png", 64);
+    push_u32(&mut data, 21);
+    push_u32(&mut data, 3_600_000_100);
+    data.extend_from_slice(b"8BIM");
+    data.extend_from_slice(b"PNGf");
+    push_u16(&mut data, 0xffff);
+    push_u16(&mut data, 0xffff);
+    push_u32(&mut data, 0);
+    data.extend_from_slice(&[0; 12]);
+
+    push_extra(&mut data, 0, b".background");
+    push_extra(&mut data, 1, &[0, 0, 0, 18]);
+    push_extra(&mut data, 2, b"Installer:.background:bg.png");
+    push_extra(&mut data, 14, &unicode_name("bg.png"));
+    push_extra(&mut data, 15, &unicode_name("Installer"));
+    push_extra(&mut data, 18, b"/.background/bg.png");
+    push_extra(&mut data, 19, b"/Volumes/Installer");
+    push_extra(&mut data, 7, &[1, 2, 3]);
+    finish(data)
+}
+
+/// A version 3 alias, whose names are only stored as extras.
+fn version_3() -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(b"APPL");
+    push_u16(&mut data, 0);
+    push_u16(&mut data, 3);
+    push_u16(&mut data, 1); // a folder
+    data.extend_from_slice(&(3_600_000_000i64 * 65536 + 32768).to_be_bytes());
+    data.extend_from_slice(b"H+\0\0");
+    push_u16(&mut data, 0);
+    push_u32(&mut data, 2);
+    push_u32(&mut data, 40);
+    data.extend_from_slice(&(3_600_000_100i64 * 65536).to_be_bytes());
+    push_u32(&mut data, 0x80);
+    data.extend_from_slice(&[0; 14]);
+
+    push_extra(&mut data, 14, &unicode_name("背景 Bilder"));
+    push_extra(&mut data, 15, &unicode_name("Macintosh HD"));
+    push_extra(&mut data, 18, b"/Users/me/\xe8\x83\x8c\xe6\x99\xaf Bilder");
+    finish(data)
+}
+
+#[test]
+fn parses_version_2() {
+    let alias = alias::parse(&version_2()).expect("Could not parse the alias.");
+    assert_eq!(alias.version, 2);
+    assert_eq!(alias.kind, AliasKind::File);
+    assert_eq!(alias.volume.name, "Installer");
+    assert_eq!(alias.volume.created, Timestamp::from_ticks(3_600_000_000 * 65536));
+    assert_eq!(&alias.volume.fs_type, b"H+\0\0");
+    assert_eq!(alias.volume.disk_type, 5);
+    assert_eq!(alias.target.name, "bg.png");
+    assert_eq!(alias.target.id, 21);
+    assert_eq!(alias.target.parent_id, 18);
+    assert_eq!(alias.target.created, Timestamp::from_ticks(3_600_000_100 * 65536));
+    assert_eq!(&alias.target.file_type, b"PNGf");
+    assert_eq!(&alias.target.creator, b"8BIM");
+    assert_eq!((alias.target.levels_from, alias.target.levels_to), (-1, -1));
+
+    assert_eq!(alias.extras.len(), 8);
+    assert_eq!(alias.extras[0], AliasExtra::ParentName(".background".to_string()));
+    assert_eq!(alias.extras[1], AliasExtra::CnidPath(vec![18]));
+    assert_eq!(alias.extras[7], AliasExtra::Other(7, vec![1, 2, 3]));
+    assert_eq!(alias.carbon_path(), Some("Installer:.background:bg.png"));
+    assert_eq!(alias.posix_path(), Some("/.background/bg.png"));
+    assert_eq!(alias.posix_mount_point(), Some("/Volumes/Installer"));
+    assert_eq!(alias.full_posix_path(), Some("/Volumes/Installer/.background/bg.png".to_string()));
+}
+
+#[test]
+fn parses_version_3() {
+    let alias = alias::parse(&version_3()).expect("Could not parse the alias.");
+    assert_eq!(alias.version, 3);
+    assert_eq!(&alias.app_info, b"APPL");
+    assert_eq!(alias.kind, AliasKind::Folder);
+    assert_eq!(alias.volume.name, "Macintosh HD");
+    assert_eq!(alias.volume.created.fraction(), 32768);
+    assert_eq!(alias.volume.attributes, 0x80);
+    assert_eq!(alias.target.name, "背景 Bilder");
+    assert_eq!(alias.target.id, 40);
+    assert_eq!(alias.target.parent_id, 2);
+    assert_eq!(alias.posix_path(), Some("/Users/me/背景 Bilder"));
+    assert_eq!(alias.full_posix_path(), None);
+}
+
+#[test]
+fn mac_roman_names() {
+    let mut data = version_2();
+    // "Installer" becomes "Inst\u{e4}ller", without the Unicode volume name to take its place.
+    data[8 + 2 + 5] = 0x8a;
+    let start = data.windows(2).position(|w| w == [0, 15]).expect("No Unicode volume name.");
+    data[start + 1] = 99;
+    let alias = alias::parse(&data).expect("Could not parse the alias.");
+    assert_eq!(alias.volume.name, "Inst\u{e4}ller");
+}
+
+#[test]
+fn rejects_bad_aliases() {
+    let data = version_2();
+    assert!(alias::parse(&data[..100]).is_err());
+    let mut wrong_version = data.clone();
+    wrong_version[7] = 4;
+    assert!(alias::parse(&wrong_version).is_err());
+    let mut unterminated = data.clone();
+    let len = unterminated.len();
+    unterminated.truncate(len - 4);
+    let size = (len - 4) as u16;
+    unterminated[4..6].copy_from_slice(&size.to_be_bytes());
+    assert!(alias::parse(&unterminated).is_err());
+}
+
+#[test]
+fn picture_background_in_a_store() {
+    let data = version_2();
+    let mut records = HashMap::new();
+    records.insert("BKGD", RecordValue::Background(BackgroundType::Picture(data.len() as u32, None)));
+    records.insert("pict", RecordValue::Slice(&data));
+    let mut contents = HashMap::new();
+    contents.insert(".".to_string(), records);
+    let file = DsStore::from_contents(contents).to_bytes().expect("Could not write the store.");
+
+    let store = DsStore::new(&file).expect("Could not parse the store.");
+    let expected: Alias = alias::parse(&data).expect("Could not parse the alias.");
+    match store.contents()["."]["BKGD"] {
+        RecordValue::Background(BackgroundType::Picture(length, Some(ref alias))) => {
+            assert_eq!(length as usize, data.len());
+            assert_eq!(alias, &expected);
+        },
+        ref other => panic!("Unexpected value {:?}", other),
+    }
+    match store.contents()["."]["pict"] {
+        RecordValue::Alias(ref record) => {
+            assert_eq!(record.value(), &expected);
+            assert_eq!(record.source(), Some(&data[..]));
+        },
+        ref other => panic!("Unexpected value {:?}", other),
+    }
+    assert_eq!(store.to_bytes_lossless().expect("Could not write the store."), file);
+}
+
+#[test]
+fn writes_what_it_parses() {
+    for data in &[version_2(), version_3()] {
+        let alias = alias::parse(data).expect("Could not parse the alias.");
+        assert_eq!(&alias::write(&alias), data);
+    }
+}
+
+#[test]
+fn new_alias() {
+    let volume_created = Timestamp::from_ticks(3_600_000_000 * 65536 + 1);
+    let alias = Alias::new("Installer", ".background/bg.png", Some(volume_created), None);
+    let data = alias::write(&alias);
+    assert_eq!(&data[4..6], &(data.len() as u16).to_be_bytes());
+    assert_eq!(&data[6..8], &[0, 2]);
+
+    let parsed = alias::parse(&data).expect("Could not parse the alias.");
+    assert_eq!(parsed, alias);
+    assert_eq!(parsed.volume.name, "Installer");
+    assert_eq!(parsed.volume.created, volume_created);
+    assert_eq!(parsed.target.name, "bg.png");
+    assert_eq!(parsed.target.created, Timestamp::from_ticks(0));
+    assert_eq!(parsed.extras[0], AliasExtra::ParentName(".background".to_string()));
+    assert_eq!(parsed.carbon_path(), Some("Installer:.background:bg.png"));
+    assert_eq!(parsed.full_posix_path(), Some("/Volumes/Installer/.background/bg.png".to_string()));
+}
+
+#[test]
+fn long_and_unicode_names() {
+    let volume_name = "Ünïcödé Installer with a name longer than 27 bytes";
+    let alias = Alias::new(volume_name, "/背景/bg:1.png", None, None);
+    let data = alias::write(&alias);
+    // The fixed-size part holds as much of the name as fits, in Mac OS Roman.
+    assert_eq!(data[10], 27);
+    assert_eq!(data[11], 0x86);
+
+    let parsed = alias::parse(&data).expect("Could not parse the alias.");
+    assert_eq!(parsed.volume.name, volume_name);
+    assert_eq!(parsed.target.name, "bg:1.png");
+    // The Carbon path is in Mac OS Roman too, which has no CJK characters.
+    assert_eq!(parsed.carbon_path(), Some("Ünïcödé Installer with a name longer than 27 bytes:??:bg/1.png"));
+    assert_eq!(parsed.posix_path(), Some("/背景/bg:1.png"));
+}
+
+#[test]
+fn set_background_picture() {
+    let mut store = DsStore::default();
+    let alias = Alias::new("Installer", ".background/bg.png", None, None);
+    store.set_background_picture(".", alias.clone());
+    let file = store.to_bytes().expect("Could not write the store.");
+
+    let store = DsStore::new(&file).expect("Could not parse the store.");
+    match store.contents()["."]["BKGD"] {
+        RecordValue::Background(BackgroundType::Picture(length, Some(ref read))) => {
+            assert_eq!(length as usize, alias::write(&alias).len());
+            assert_eq!(read, &alias);
+        },
+        ref other => panic!("Unexpected value {:?}", other),
+    }
+    let options = store.icon_view_options(".").expect("No icvp record.");
+    assert_eq!(options.background_type, Some(IconViewBackground::Picture));
+    let image_alias = options.background_image_alias.expect("No background alias.");
+    assert_eq!(alias::parse(&image_alias).expect("Could not parse the alias."), alias);
+}
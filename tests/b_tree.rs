@@ -0,0 +1,47 @@
+extern crate ds_store;
+
+mod common;
+
+use ds_store::{DsStore, RecordValue};
+use ds_store::allocator::Allocator;
+use common::{file_name, synthetic_store};
+
+fn check_tree(count: usize, min_internals: u32) {
+    let written = synthetic_store(count).to_bytes().expect("Could not write the DS_Store.");
+    let allocator = Allocator::new(&written).expect("Could not read the allocator.");
+    let directory = allocator.traverse().expect("Could not traverse the B-Tree.");
+
+    assert!(directory.num_internals >= min_internals, "only {} internal levels", directory.num_internals);
+    assert_eq!(directory.num_records as usize, count * 2);
+    assert_eq!(directory.num_nodes as usize, directory.nodes.len());
+    assert_eq!(directory.contents.len(), count);
+    for i in 0..count {
+        let metadata = &directory.contents[&file_name(i)];
+        match metadata["cmmt"] {
+            RecordValue::String(ref s) => assert_eq!(s, &format!("comment {}", i)),
+            ref other => panic!("Unexpected value {:?}", other),
+        }
+        match metadata["fwsw"] {
+            RecordValue::I32(n) => assert_eq!(n, i as i32),
+            ref other => panic!("Unexpected value {:?}", other),
+        }
+    }
+
+    let store = DsStore::new(&written).expect("Could not construct the DS_Store.");
+    assert_eq!(store.to_bytes_lossless().expect("Could not write the DS_Store."), written);
+}
+
+#[test]
+fn single_leaf() {
+    check_tree(3, 0);
+}
+
+#[test]
+fn two_levels() {
+    check_tree(40, 1);
+}
+
+#[test]
+fn several_levels() {
+    check_tree(1500, 3);
+}
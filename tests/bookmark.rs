@@ -0,0 +1,236 @@
+extern crate ds_store;
+
+use std::collections::HashMap;
+use ds_store::{DsStore, Error, RecordValue};
+use ds_store::bookmark::{self, keys, BookmarkItem, BookmarkKey};
+
+/// Assembles the data after a bookmark's header, which starts with the offset of the first table of contents.
+struct Builder {
+    data: Vec<u8>,
+}
+
+impl Builder {
+    fn new() -> Builder {
+        Builder {data: vec![0; 4]}
+    }
+
+    fn push_u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn item(&mut self, type_code: u32, value: &[u8]) -> u32 {
+        let offset = self.data.len() as u32;
+        self.push_u32(value.len() as u32);
+        self.push_u32(type_code);
+        self.data.extend_from_slice(value);
+        while self.data.len() % 4 != 0 {
+            self.data.push(0);
+        }
+        offset
+    }
+
+    fn string(&mut self, s: &str) -> u32 {
+        self.item(0x0101, s.as_bytes())
+    }
+
+    fn int64(&mut self, n: i64) -> u32 {
+        self.item(0x0304, &n.to_le_bytes())
+    }
+
+    fn date(&mut self, seconds: f64) -> u32 {
+        self.item(0x0400, &seconds.to_be_bytes())
+    }
+
+    fn offsets(&mut self, type_code: u32, offsets: &[u32]) -> u32 {
+        let value: Vec<u8> = offsets.iter().flat_map(|o| o.to_le_bytes().to_vec()).collect();
+        self.item(type_code, &value)
+    }
+
+    /// Adds a table of contents, pointed to by `previous` (the start of the data for the first one).
+    fn toc(&mut self, previous: usize, id: u32, entries: &[(u32, u32)]) -> usize {
+        let offset = self.data.len();
+        self.data[previous..previous + 4].copy_from_slice(&(offset as u32).to_le_bytes());
+        self.push_u32(12 + 12 * entries.len() as u32);
+        self.push_u32(0xffff_fffe);
+        self.push_u32(id);
+        self.push_u32(0);
+        self.push_u32(entries.len() as u32);
+        for &(key, item) in entries {
+            self.push_u32(key);
+            self.push_u32(item);
+            self.push_u32(0);
+        }
+        offset + 12
+    }
+
+    fn finish(self) -> Vec<u8> {
+        let mut bookmark = Vec::new();
+        bookmark.extend_from_slice(b"book");
+        bookmark.extend_from_slice(&(48 + self.data.len() as u32).to_le_bytes());
+        bookmark.extend_from_slice(&0x1004_0000u32.to_le_bytes());
+        bookmark.extend_from_slice(&48u32.to_le_bytes());
+        bookmark.extend_from_slice(&[0; 32]);
+        bookmark.extend_from_slice(&self.data);
+        bookmark
+    }
+}
+
+/// A bookmark to "/Volumes/Installer/.background/背景.png", like Finder makes in "pBBk" records.
+fn background_bookmark() -> Vec<u8> {
+    let mut builder = Builder::new();
+    let components: Vec<u32> = ["Volumes", "Installer", ".background", "背景.png"].iter()
+        .map(|c| builder.string(c))
+        .collect();
+    let path = builder.offsets(0x0601, &components);
+    let ids: Vec<u32> = [1, 2, 18, 21].iter().map(|&id| builder.int64(id)).collect();
+    let id_path = builder.offsets(0x0601, &ids);
+    let created = builder.date(600_000_000.5);
+    let volume_name = builder.string("Installer");
+    let volume_uuid = builder.string("0A81F3B1-51D9-3335-B3E3-169C3640360D");
+    let volume_url = builder.item(0x0901, b"file:///Volumes/Installer/");
+    let relative = builder.string(".background/");
+    let relative_url = builder.offsets(0x0902, &[volume_url, relative]);
+    let volume_created = builder.date(599_000_000.0);
+    let is_root = builder.item(0x0500, &[]);
+    let user_key = builder.string("UserKey");
+    let null = builder.item(0x0a01, &[]);
+    let key = builder.string("width");
+    let width = builder.item(0x0303, &640i32.to_le_bytes());
+    let properties = builder.offsets(0x0701, &[key, width]);
+
+    let first = builder.toc(0, 1, &[
+        (keys::PATH, path),
+        (keys::FILE_ID_PATH, id_path),
+        (keys::FILE_CREATION_DATE, created),
+        (keys::VOLUME_NAME, volume_name),
+        (keys::VOLUME_UUID, volume_uuid),
+        (keys::VOLUME_URL, volume_url),
+        (keys::VOLUME_CREATION_DATE, volume_created),
+        (keys::VOLUME_IS_ROOT, is_root),
+    ]);
+    builder.toc(first, 2, &[
+        (0x8000_0000 | user_key, null),
+        (0xf017, properties),
+        (0xf018, relative_url),
+    ]);
+    builder.finish()
+}
+
+#[test]
+fn parses_a_bookmark() {
+    let bookmark = bookmark::parse(&background_bookmark()).expect("Could not parse the bookmark.");
+    assert_eq!(bookmark.tocs.len(), 2);
+    assert_eq!(bookmark.path(), Some(vec!["Volumes", "Installer", ".background", "背景.png"]));
+    assert_eq!(bookmark.full_path(), Some("/Volumes/Installer/.background/背景.png".to_string()));
+    assert_eq!(bookmark.file_ids(), Some(vec![1, 2, 18, 21]));
+    assert_eq!(bookmark.creation_date(), Some(600_000_000.5));
+    assert_eq!(bookmark.volume_name(), Some("Installer"));
+    assert_eq!(bookmark.volume_uuid(), Some("0A81F3B1-51D9-3335-B3E3-169C3640360D"));
+    assert_eq!(bookmark.volume_url(), Some("file:///Volumes/Installer/".to_string()));
+    assert_eq!(bookmark.volume_creation_date(), Some(599_000_000.0));
+    assert_eq!(bookmark.get(keys::VOLUME_IS_ROOT), Some(&BookmarkItem::Boolean(false)));
+
+    let second = &bookmark.tocs[1];
+    assert_eq!(second.0, 2);
+    assert_eq!(second.1[0], (BookmarkKey::Name("UserKey".to_string()), BookmarkItem::Null));
+    assert_eq!(bookmark.get(0xf017), Some(&BookmarkItem::Dictionary(vec![
+        (BookmarkItem::String("width".to_string()), BookmarkItem::Integer(640)),
+    ])));
+    assert_eq!(bookmark.get(0xf018).and_then(BookmarkItem::as_url),
+               Some("file:///Volumes/Installer/.background/".to_string()));
+}
+
+#[test]
+fn rejects_bad_bookmarks() {
+    let data = background_bookmark();
+    assert!(bookmark::parse(&data[..40]).is_err());
+    assert!(bookmark::parse(b"alis and more than sixteen bytes").is_err());
+
+    // An array containing itself.
+    let mut builder = Builder::new();
+    let array = builder.offsets(0x0601, &[4]);
+    builder.toc(0, 1, &[(keys::PATH, array)]);
+    assert!(bookmark::parse(&builder.finish()).is_err());
+}
+
+#[test]
+fn rejects_truncated_offsets() {
+    for &type_code in &[0x0601, 0x0701, 0x0902] {
+        let mut builder = Builder::new();
+        let string = builder.string("a");
+        let item = builder.item(type_code, &string.to_le_bytes()[..3]);
+        builder.toc(0, 1, &[(keys::PATH, item)]);
+        match bookmark::parse(&builder.finish()) {
+            Err(Error::BadData(_)) => {},
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    // Kept as a blob in a store.
+    let mut builder = Builder::new();
+    let array = builder.item(0x0601, &[4, 0, 0]);
+    builder.toc(0, 1, &[(keys::PATH, array)]);
+    let data = builder.finish();
+    let mut store = DsStore::default();
+    store.set_record(".", "pBBk", RecordValue::Slice(&data)).unwrap();
+    let file = store.to_bytes().expect("Could not write the store.");
+    let store = DsStore::new(&file).expect("Could not parse the store.");
+    match store.contents()["."]["pBBk"] {
+        RecordValue::Slice(blob) => assert_eq!(blob, &data[..]),
+        ref other => panic!("Unexpected value {:?}", other),
+    }
+}
+
+#[test]
+fn shared_items_are_not_expanded_without_end() {
+    // Each array holds the next one twice, so the first one expands to 2^40 strings.
+    let mut builder = Builder::new();
+    let mut item = builder.string("a");
+    for _ in 0..40 {
+        item = builder.offsets(0x0601, &[item, item]);
+    }
+    builder.toc(0, 1, &[(keys::PATH, item)]);
+    match bookmark::parse(&builder.finish()) {
+        Err(Error::BadData(_)) => {},
+        other => panic!("Unexpected result {:?}", other.map(|_| ())),
+    }
+
+    let mut builder = Builder::new();
+    let mut item = builder.string("a");
+    for _ in 0..3 {
+        item = builder.offsets(0x0601, &[item, item]);
+    }
+    builder.toc(0, 1, &[(keys::PATH, item)]);
+    let parsed = bookmark::parse(&builder.finish()).expect("Could not parse the bookmark.");
+    let mut expected = BookmarkItem::String("a".to_string());
+    for _ in 0..3 {
+        expected = BookmarkItem::Array(vec![expected.clone(), expected]);
+    }
+    assert_eq!(parsed.get(keys::PATH), Some(&expected));
+}
+
+#[test]
+fn bookmark_records_in_a_store() {
+    let data = background_bookmark();
+    let not_a_bookmark = [1, 2, 3];
+    let mut records = HashMap::new();
+    records.insert("pBBk", RecordValue::Slice(&data));
+    records.insert("pBB0", RecordValue::Slice(&not_a_bookmark));
+    let mut contents = HashMap::new();
+    contents.insert(".".to_string(), records);
+    let file = DsStore::from_contents(contents).to_bytes().expect("Could not write the store.");
+
+    let store = DsStore::new(&file).expect("Could not parse the store.");
+    match store.contents()["."]["pBBk"] {
+        RecordValue::Bookmark(ref record) => {
+            assert_eq!(record.source(), &data[..]);
+            assert_eq!(record.value().volume_name(), Some("Installer"));
+        },
+        ref other => panic!("Unexpected value {:?}", other),
+    }
+    match store.contents()["."]["pBB0"] {
+        RecordValue::Slice(blob) => assert_eq!(blob, &not_a_bookmark),
+        ref other => panic!("Unexpected value {:?}", other),
+    }
+    assert_eq!(store.to_bytes_lossless().expect("Could not write the store."), file);
+}
@@ -0,0 +1,66 @@
+// Each test file uses only some of these helpers.
+#![allow(dead_code)]
+
+use std::fs::File;
+use std::io::Read;
+use ds_store::{DsStore, RecordValue};
+
+pub fn read_example() -> Vec<u8> {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/basic.DS_Store");
+    let mut buf = vec![];
+    File::open(path).expect("Could not open file.").read_to_end(&mut buf).expect("Could not read file to end.");
+    buf
+}
+
+/// The name of the `i`th file of `synthetic_store`.
+/// Long filenames keep the number of records per node low, so the tree gets deep quickly.
+pub fn file_name(i: usize) -> String {
+    format!("{:05}-{}", i, "x".repeat(200))
+}
+
+/// Builds a store with `count` files, each with a comment and a `fwsw` record.
+pub fn synthetic_store<'a>(count: usize) -> DsStore<'a> {
+    let mut store = DsStore::default();
+    for i in 0..count {
+        store.set_record(&file_name(i), "cmmt", RecordValue::String(format!("comment {}", i))).unwrap();
+        store.set_record(&file_name(i), "fwsw", RecordValue::I32(i as i32)).unwrap();
+    }
+    store
+}
+
+/// A store deep enough to have several levels of internal nodes.
+pub fn deep_store() -> Vec<u8> {
+    synthetic_store(1500).to_bytes().expect("Could not write the store.")
+}
+
+/// A .DS_Store file with a single `code` blob record for `file_name`, holding `blob`.
+/// The record is written as a "pict" record, which takes any blob, and then renamed.
+pub fn file_with_blob(file_name: &str, code: &[u8; 4], blob: &[u8]) -> Vec<u8> {
+    let mut store = DsStore::default();
+    store.set_record(file_name, "pict", RecordValue::Slice(blob)).unwrap();
+    let mut file = store.to_bytes().expect("Could not write the store.");
+
+    let positions: Vec<usize> = file.windows(4).enumerate()
+        .filter(|&(_, window)| window == b"pict")
+        .map(|(i, _)| i)
+        .collect();
+    assert_eq!(positions.len(), 1);
+    file[positions[0]..positions[0] + 4].copy_from_slice(code);
+    file
+}
+
+/// A .DS_Store file whose "cmmt" records of "b" and "a" are stored in that order,
+/// unlike the order `to_bytes` writes them in.
+pub fn unsorted_store() -> Vec<u8> {
+    let mut store = DsStore::default();
+    for file_name in &["a", "b"] {
+        store.set_record(file_name, "cmmt", RecordValue::String("x".to_string())).unwrap();
+    }
+    let mut file = store.to_bytes().expect("Could not write the store.");
+
+    // Both names are one UTF-16 unit long, so swapping them swaps the records.
+    let position = |name: u8| file.windows(10).position(|window| window == [0, 0, 0, 1, 0, name, b'c', b'm', b'm', b't']).unwrap() + 5;
+    let (a, b) = (position(b'a'), position(b'b'));
+    file.swap(a, b);
+    file
+}
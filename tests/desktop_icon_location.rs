@@ -0,0 +1,67 @@
+extern crate ds_store;
+
+mod common;
+
+use ds_store::{DesktopCoordinate, DesktopIconLocation, DsStore, RecordValue};
+use common::file_with_blob;
+
+#[test]
+fn desktop_icon_location() {
+    let mut blob = Vec::new();
+    blob.extend_from_slice(&[0, 0, 0x10, 0]);
+    blob.extend_from_slice(&[0, 0, 0, 0]);
+    blob.extend_from_slice(&(-74i32).to_be_bytes());
+    blob.extend_from_slice(&42i32.to_be_bytes());
+    blob.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0, 0, 0, 0, 0, 0, 7]);
+    let file = file_with_blob("Macintosh HD", b"dilc", &blob);
+
+    let store = DsStore::new(&file).expect("Could not parse the store.");
+    let location = store.desktop_icon_location("Macintosh HD").expect("No desktop icon location.");
+    assert_eq!(location, DesktopIconLocation {
+        flags: 0x1000,
+        unknown: [0; 4],
+        x: -74,
+        y: 42,
+        trailing: [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0, 0, 0, 0, 0, 0, 7],
+    });
+    // Flags don't change how the position is measured.
+    assert_eq!(location.horizontal(), DesktopCoordinate::FromEnd(74));
+    assert_eq!(location.vertical(), DesktopCoordinate::FromStart(42));
+    assert_eq!(store.to_bytes_lossless().expect("Could not write the store."), file);
+}
+
+#[test]
+fn pixels_from_the_top_left_corner() {
+    let location = DesktopIconLocation {flags: 0, unknown: [0; 4], x: 10, y: 0, trailing: [0; 16]};
+    assert_eq!(location.horizontal(), DesktopCoordinate::FromStart(10));
+    assert_eq!(location.vertical(), DesktopCoordinate::FromStart(0));
+    assert_eq!(location.absolute(1440, 900), (10, 0));
+    assert_eq!(location.absolute(2560, 1440), (10, 0));
+}
+
+#[test]
+fn pixels_from_the_bottom_right_corner() {
+    let location = DesktopIconLocation {flags: 0, unknown: [0; 4], x: -74, y: 42, trailing: [0; 16]};
+    assert_eq!(location.absolute(1440, 900), (1366, 42));
+    assert_eq!(location.absolute(2560, 1440), (2486, 42));
+
+    let bottom_left = DesktopIconLocation {x: 10, y: -100, ..location};
+    assert_eq!(bottom_left.vertical(), DesktopCoordinate::FromEnd(100));
+    assert_eq!(bottom_left.absolute(1440, 900), (10, 800));
+
+    let corner = DesktopIconLocation {flags: 0x3, x: i32::MIN, y: -1, ..location};
+    assert_eq!(corner.horizontal(), DesktopCoordinate::FromEnd(1 << 31));
+    assert_eq!(corner.absolute(1440, 900), (1440 - (1 << 31), 899));
+}
+
+#[test]
+fn new_desktop_icon_location() {
+    let location = DesktopIconLocation {flags: 0x3, unknown: [0; 4], x: -120, y: 500, trailing: [0; 16]};
+    let mut store = DsStore::default();
+    store.set_record("Macintosh HD", "dilc", RecordValue::DesktopIconLocation(location)).expect("Could not set the record.");
+    assert!(store.set_record("Macintosh HD", "dilc", RecordValue::Slice(&[0; 32])).is_err());
+    let file = store.to_bytes().expect("Could not write the store.");
+
+    let store = DsStore::new(&file).expect("Could not parse the store.");
+    assert_eq!(store.desktop_icon_location("Macintosh HD"), Some(location));
+}
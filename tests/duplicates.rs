@@ -0,0 +1,78 @@
+extern crate ds_store;
+
+use ds_store::{DsStore, Duplicates, Error, IconLocation, RecordValue};
+
+/// A .DS_Store file holding the "Iloc" record of "dupeA" twice, at (10, 20) and then at (30, 40).
+/// The second record is written for "dupeB", and then renamed.
+fn file_with_duplicate() -> Vec<u8> {
+    let mut store = DsStore::default();
+    store.set_icon_location("dupeA", 10, 20);
+    store.set_icon_location("dupeB", 30, 40);
+    store.set_icon_location("other", 50, 60);
+    let mut file = store.to_bytes().expect("Could not write the store.");
+
+    let name: Vec<u8> = "dupeB".encode_utf16().flat_map(|unit| unit.to_be_bytes().to_vec()).collect();
+    let positions: Vec<usize> = file.windows(name.len()).enumerate()
+        .filter(|&(_, window)| window == &name[..])
+        .map(|(i, _)| i)
+        .collect();
+    assert_eq!(positions.len(), 1);
+    let last = positions[0] + name.len() - 1;
+    file[last] = b'A';
+    file
+}
+
+fn icon_location(value: &RecordValue) -> IconLocation {
+    match *value {
+        RecordValue::IconLocation(location) => location,
+        ref other => panic!("Unexpected value {:?}", other),
+    }
+}
+
+#[test]
+fn duplicates_are_kept() {
+    let file = file_with_duplicate();
+    let store = DsStore::new(&file).expect("Could not parse the store.");
+    assert_eq!(store.icon_location("dupeA"), Some(IconLocation::new(10, 20)));
+    assert_eq!(store.records().len(), 2);
+
+    let duplicates = store.duplicates();
+    assert_eq!(duplicates.len(), 1);
+    assert_eq!(duplicates[0].file_name, "dupeA");
+    assert_eq!(duplicates[0].code, "Iloc");
+    let occurrences = &duplicates[0].occurrences;
+    assert_eq!(occurrences.len(), 2);
+    assert_eq!(icon_location(&occurrences[0].1), IconLocation::new(10, 20));
+    assert_eq!(icon_location(&occurrences[1].1), IconLocation::new(30, 40));
+    // Both are in the only node, one after the other.
+    assert_eq!(occurrences[0].0.block_id, occurrences[1].0.block_id);
+    assert_eq!((occurrences[0].0.index, occurrences[1].0.index), (0, 1));
+
+    // Lossless writing keeps both, while `to_bytes` only writes the first.
+    assert_eq!(store.to_bytes_lossless().expect("Could not write the store."), file);
+    let written = store.to_bytes().expect("Could not write the store.");
+    let store = DsStore::new(&written).expect("Could not parse the written store.");
+    assert!(store.duplicates().is_empty());
+    assert_eq!(store.icon_location("dupeA"), Some(IconLocation::new(10, 20)));
+}
+
+#[test]
+fn duplicates_are_rejected() {
+    let file = file_with_duplicate();
+    match DsStore::with_duplicates(&file, Duplicates::Reject) {
+        Err(Error::DuplicateRecord(file_name, code)) => assert_eq!((file_name.as_str(), code), ("dupeA", "Iloc")),
+        Err(other) => panic!("Unexpected error {:?}", other),
+        Ok(_) => panic!("The duplicate was not rejected."),
+    }
+}
+
+#[test]
+fn files_without_duplicates() {
+    let file = file_with_duplicate();
+    let mut store = DsStore::new(&file).expect("Could not parse the store.");
+    store.set_icon_location("dupeA", 70, 80);
+    let file = store.to_bytes().expect("Could not write the store.");
+    let strict = DsStore::with_duplicates(&file, Duplicates::Reject).expect("Could not parse the store.");
+    assert!(strict.duplicates().is_empty());
+    assert_eq!(strict.icon_location("dupeA"), Some(IconLocation::new(70, 80)));
+}
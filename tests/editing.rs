@@ -0,0 +1,183 @@
+extern crate ds_store;
+
+mod common;
+
+use ds_store::{DsStore, Error, GenericValue, IconLocation, RecordValue};
+use ds_store::allocator::StyleType;
+use common::read_example;
+
+fn codes<'a>(store: &DsStore<'a>, file_name: &str) -> Vec<&'a str> {
+    store.contents().get(file_name).map_or(vec![], |metadata| metadata.keys().cloned().collect())
+}
+
+#[test]
+fn sets_records() {
+    let mut store = DsStore::default();
+    assert!(store.set_record("a", "cmmt", RecordValue::String("first".to_string())).unwrap().is_none());
+    assert!(store.set_record("a", "vstl", RecordValue::Style(StyleType::List)).unwrap().is_none());
+    match store.set_record("a", "cmmt", RecordValue::String("second".to_string())).unwrap() {
+        Some(RecordValue::String(ref s)) => assert_eq!(s, "first"),
+        other => panic!("Unexpected previous value {:?}", other),
+    }
+    assert_eq!(codes(&store, "a"), vec!["cmmt", "vstl"]);
+    match store.contents()["a"]["cmmt"] {
+        RecordValue::String(ref s) => assert_eq!(s, "second"),
+        ref other => panic!("Unexpected value {:?}", other),
+    }
+
+    let written = store.to_bytes().expect("Could not write the store.");
+    let read = DsStore::new(&written).expect("Could not read the store.");
+    assert_eq!(format!("{:?}", read.contents()), format!("{:?}", store.contents()));
+}
+
+#[test]
+fn rejects_mismatched_values() {
+    let mut store = DsStore::default();
+    store.set_record("a", "cmmt", RecordValue::String("comment".to_string())).unwrap();
+    let before = format!("{:?}", store.contents());
+
+    // A known code with the wrong type of value.
+    match store.set_record("a", "cmmt", RecordValue::I32(1)) {
+        Err(Error::BadData(_)) => {},
+        other => panic!("Unexpected result {:?}", other),
+    }
+    match store.set_record("b", "vstl", RecordValue::String("Nlsv".to_string())) {
+        Err(Error::BadData(_)) => {},
+        other => panic!("Unexpected result {:?}", other),
+    }
+    // A blob of the wrong length.
+    match store.set_record("a", "icgo", RecordValue::Slice(&[0; 7])) {
+        Err(Error::BadData(_)) => {},
+        other => panic!("Unexpected result {:?}", other),
+    }
+    // Codes that are not four bytes long, and unknown codes with anything but an `Unknown` value of that code.
+    match store.set_record("a", "cmt", RecordValue::String("comment".to_string())) {
+        Err(Error::UnkonwnStructureType(code)) => assert_eq!(code, b"cmt"),
+        other => panic!("Unexpected result {:?}", other),
+    }
+    match store.set_record("a", "zzzz", RecordValue::I32(1)) {
+        Err(Error::UnkonwnStructureType(code)) => assert_eq!(code, b"zzzz"),
+        other => panic!("Unexpected result {:?}", other),
+    }
+    match store.set_record("a", "zzzz", RecordValue::Unknown("yyyy", GenericValue::Long(1))) {
+        Err(Error::UnkonwnStructureType(code)) => assert_eq!(code, b"zzzz"),
+        other => panic!("Unexpected result {:?}", other),
+    }
+    match store.set_record("a", "zzzz", RecordValue::Unknown("zzzz", GenericValue::Type(b"abc"))) {
+        Err(Error::BadData(_)) => {},
+        other => panic!("Unexpected result {:?}", other),
+    }
+
+    // Nothing changed, not even an empty entry for "b".
+    assert_eq!(format!("{:?}", store.contents()), before);
+    assert!(store.set_record("a", "zzzz", RecordValue::Unknown("zzzz", GenericValue::Long(1))).unwrap().is_none());
+}
+
+#[test]
+fn removes_records() {
+    let mut store = DsStore::default();
+    store.set_icon_location("a", 1, 2);
+    store.set_record("a", "cmmt", RecordValue::String("comment".to_string())).unwrap();
+
+    assert!(store.remove_record("a", "vstl").is_none());
+    assert!(store.remove_record("b", "Iloc").is_none());
+    match store.remove_record("a", "Iloc") {
+        Some(RecordValue::IconLocation(location)) => assert_eq!(location, IconLocation::new(1, 2)),
+        other => panic!("Unexpected value {:?}", other),
+    }
+    assert!(store.remove_record("a", "Iloc").is_none());
+    assert_eq!(codes(&store, "a"), vec!["cmmt"]);
+
+    // Removing the last record of a file removes the file.
+    assert!(store.remove_record("a", "cmmt").is_some());
+    assert!(store.contents().is_empty());
+}
+
+#[test]
+fn removes_files() {
+    let mut store = DsStore::default();
+    store.set_icon_location("a", 1, 2);
+    store.set_record("a", "cmmt", RecordValue::String("comment".to_string())).unwrap();
+    store.set_icon_location("b", 3, 4);
+
+    assert!(store.remove_file("c").is_none());
+    let removed = store.remove_file("a").expect("No records removed.");
+    assert_eq!(removed.keys().cloned().collect::<Vec<_>>(), vec!["Iloc", "cmmt"]);
+    assert!(store.remove_file("a").is_none());
+    assert_eq!(store.contents().keys().collect::<Vec<_>>(), vec!["b"]);
+}
+
+#[test]
+fn renames_files() {
+    let mut store = DsStore::default();
+    store.set_icon_location("a", 1, 2);
+    store.set_record("a", "cmmt", RecordValue::String("comment".to_string())).unwrap();
+    store.set_record("b", "vstl", RecordValue::Style(StyleType::Icon)).unwrap();
+
+    assert!(!store.rename_file("c", "d"));
+    assert!(store.contents().get("d").is_none());
+
+    assert!(store.rename_file("a", "c"));
+    assert!(store.contents().get("a").is_none());
+    assert_eq!(codes(&store, "c"), vec!["Iloc", "cmmt"]);
+    assert_eq!(store.icon_location("c"), Some(IconLocation::new(1, 2)));
+
+    // The records of the target are replaced, not merged.
+    assert!(store.rename_file("c", "b"));
+    assert_eq!(codes(&store, "b"), vec!["Iloc", "cmmt"]);
+    assert_eq!(store.contents().keys().collect::<Vec<_>>(), vec!["b"]);
+}
+
+#[test]
+fn edits_a_read_store() {
+    let buf = read_example();
+    let mut store = DsStore::new(&buf).expect("Could not parse the store.");
+    let file_name = store.contents().keys().next().expect("No files in the example.").clone();
+    let count = store.contents()[&file_name].len();
+
+    store.set_record(&file_name, "cmmt", RecordValue::String("added".to_string())).unwrap();
+    assert!(store.rename_file(&file_name, "renamed"));
+    let written = store.to_bytes().expect("Could not write the store.");
+    let read = DsStore::new(&written).expect("Could not read the store.");
+    assert!(read.contents().get(&file_name).is_none());
+    assert_eq!(read.contents()["renamed"].len(), count + 1);
+    match read.contents()["renamed"]["cmmt"] {
+        RecordValue::String(ref s) => assert_eq!(s, "added"),
+        ref other => panic!("Unexpected value {:?}", other),
+    }
+}
+
+#[test]
+fn names_that_only_differ_in_case_are_the_same_file() {
+    let mut store = DsStore::default();
+    store.set_record("File", "cmmt", RecordValue::String("comment".to_string())).unwrap();
+    store.set_record("file", "vstl", RecordValue::Style(StyleType::List)).unwrap();
+    store.set_icon_location("FILE", 1, 2);
+    assert_eq!(store.contents().keys().collect::<Vec<_>>(), vec!["File"]);
+    assert_eq!(codes(&store, "File"), vec!["Iloc", "cmmt", "vstl"]);
+
+    // Renaming replaces a file that only differs in case, and can change the case of a name.
+    store.set_icon_location("other", 3, 4);
+    assert!(store.rename_file("other", "fILE"));
+    assert_eq!(store.contents().keys().collect::<Vec<_>>(), vec!["fILE"]);
+    assert_eq!(store.icon_location("fILE"), Some(IconLocation::new(3, 4)));
+    assert!(store.rename_file("fILE", "File"));
+    assert_eq!(store.contents().keys().collect::<Vec<_>>(), vec!["File"]);
+    write_and_read_back(&store);
+
+    // Stores made from contents can still have both, which can't be written.
+    let both = DsStore::from_contents(vec![
+        ("A".to_string(), vec![("Iloc", RecordValue::IconLocation(IconLocation::new(1, 2)))]),
+        ("a".to_string(), vec![("Iloc", RecordValue::IconLocation(IconLocation::new(3, 4)))]),
+    ]);
+    match both.to_bytes() {
+        Err(Error::BadData(_)) => {},
+        other => panic!("Unexpected result {:?}", other.map(|file| file.len())),
+    }
+}
+
+fn write_and_read_back(store: &DsStore) {
+    let written = store.to_bytes().expect("Could not write the store.");
+    let read = DsStore::new(&written).expect("Could not read the store.");
+    assert_eq!(format!("{:?}", read.contents()), format!("{:?}", store.contents()));
+}
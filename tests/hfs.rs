@@ -0,0 +1,77 @@
+extern crate ds_store;
+
+use std::cmp::Ordering;
+use ds_store::DsStore;
+use ds_store::hfs::{compare_file_names, compare_keys, fast_unicode_compare};
+
+#[test]
+fn mixed_case() {
+    assert_eq!(compare_file_names("ReadMe.TXT", "readme.txt"), Ordering::Equal);
+    assert_eq!(compare_file_names("Zebra", "apple"), Ordering::Greater);
+    assert_eq!(compare_file_names("a", "B"), Ordering::Less);
+    // Underscore is between the uppercase and lowercase letters, so it sorts before folded letters.
+    assert_eq!(compare_file_names("_", "A"), Ordering::Less);
+    // A name sorts before the longer names it starts.
+    assert_eq!(compare_file_names("File", "file 2"), Ordering::Less);
+    // Letters outside of ASCII fold too.
+    assert_eq!(compare_file_names("ÆØÞ", "æøþ"), Ordering::Equal);
+    assert_eq!(compare_file_names("ΑΒΓ", "αβγ"), Ordering::Equal);
+    assert_eq!(compare_file_names("ПРИВЕТ", "привет"), Ordering::Equal);
+    // "Й" decomposes, so it is not folded, and sorts before every lowercase Cyrillic letter.
+    assert_eq!(compare_file_names("ЙОД", "йод"), Ordering::Less);
+    assert_eq!(compare_file_names("Й", "а"), Ordering::Less);
+    assert_eq!(compare_file_names("И\u{306}", "и\u{306}"), Ordering::Equal);
+    assert_eq!(compare_file_names("ＡＢＣ", "ａｂｃ"), Ordering::Equal);
+}
+
+#[test]
+fn accented() {
+    // Decomposed, as HFS+ stores names: the base letter folds and the accent is kept.
+    assert_eq!(compare_file_names("E\u{301}te\u{301}", "e\u{301}te\u{301}"), Ordering::Equal);
+    assert_eq!(compare_file_names("e\u{301}", "e\u{300}"), Ordering::Greater);
+    assert_eq!(compare_file_names("e\u{301}", "f"), Ordering::Less);
+    // Precomposed letters are not folded, and sort after every ASCII letter.
+    assert_eq!(compare_file_names("\u{c9}t\u{e9}", "\u{e9}t\u{e9}"), Ordering::Less);
+    assert_eq!(compare_file_names("\u{e9}", "z"), Ordering::Greater);
+}
+
+#[test]
+fn cjk() {
+    // Compared by UTF-16 unit.
+    assert_eq!(compare_file_names("背景", "背景"), Ordering::Equal);
+    assert_eq!(compare_file_names("写真", "背景"), Ordering::Less);
+    assert_eq!(compare_file_names("z", "写真"), Ordering::Less);
+    assert_eq!(compare_file_names("ひらがな", "カタカナ"), Ordering::Less);
+    // Outside of the Basic Multilingual Plane, surrogates compare as they are.
+    assert_eq!(compare_file_names("\u{20000}", "\u{ff5a}"), Ordering::Less);
+}
+
+#[test]
+fn ignorable_characters() {
+    assert_eq!(compare_file_names("a\u{200d}b", "ab"), Ordering::Equal);
+    assert_eq!(compare_file_names("\u{feff}Name", "name"), Ordering::Equal);
+    assert_eq!(compare_file_names("\u{202e}", ""), Ordering::Equal);
+    // NUL is not ignored, and sorts last.
+    assert_eq!(fast_unicode_compare(&[0x61, 0], &[0x61, 0xfffd]), Ordering::Greater);
+}
+
+#[test]
+fn keys() {
+    assert_eq!(compare_keys(("Folder", "vstl"), ("folder", "Iloc")), Ordering::Greater);
+    assert_eq!(compare_keys(("Folder", "Iloc"), ("folder", "Iloc")), Ordering::Equal);
+    assert_eq!(compare_keys(("a", "vstl"), ("B", "Iloc")), Ordering::Less);
+}
+
+#[test]
+fn written_in_hfs_order() {
+    let mut store = DsStore::default();
+    let names = ["\u{e9}cole", "Zebra", "e\u{301}cole", "背景", "apple", "_hidden", "Ёлка", "Ђак"];
+    for name in &names {
+        store.set_icon_location(name, 1, 2);
+    }
+    let file = store.to_bytes().expect("Could not write the store.");
+    let store = DsStore::new(&file).expect("Could not parse the store.");
+    let written: Vec<&str> = store.records().into_iter().map(|(name, _, _)| name).collect();
+    // "Ё" is not folded since it decomposes, while "Ђ" is folded to "ђ", after it.
+    assert_eq!(written, vec!["_hidden", "apple", "e\u{301}cole", "Zebra", "\u{e9}cole", "Ёлка", "Ђак", "背景"]);
+}
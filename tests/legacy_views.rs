@@ -0,0 +1,263 @@
+extern crate ds_store;
+
+mod common;
+
+use std::collections::HashMap;
+use ds_store::{Arrangement, DsStore, FinderInfo, IconViewOptions, LabelPosition, LegacyIconViewOptions, LegacyUpgrade, ListViewSettings,
+               RecordValue, UpgradeOutcome, WindowInfo};
+use ds_store::allocator::StyleType;
+use common::file_with_blob;
+
+fn record<'a, 'b>(store: &'b DsStore<'a>, code: &str) -> &'b RecordValue<'a> {
+    &store.contents()["."][code]
+}
+
+#[test]
+fn icvo_older_layout() {
+    let mut blob = Vec::new();
+    blob.extend_from_slice(b"icvo");
+    blob.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+    blob.extend_from_slice(&[0, 48]);
+    blob.extend_from_slice(b"grid");
+    let file = file_with_blob(".", b"icvo", &blob);
+
+    let store = DsStore::new(&file).expect("Could not parse the store.");
+    let options = match *record(&store, "icvo") {
+        RecordValue::LegacyIconView(options) => options,
+        ref other => panic!("Unexpected value {:?}", other),
+    };
+    assert_eq!(options, LegacyIconViewOptions::Icvo {unknown: [0, 0, 0, 0, 0, 0, 0, 1], icon_size: 48, arrangement: *b"grid"});
+    assert_eq!(options.icon_size(), 48);
+    assert_eq!(options.arrangement(), b"grid");
+    assert_eq!(options.label_position(), None);
+    assert_eq!(store.to_bytes_lossless().expect("Could not write the store."), file);
+}
+
+#[test]
+fn icvo_icv4_layout() {
+    let mut blob = Vec::new();
+    blob.extend_from_slice(b"icv4");
+    blob.extend_from_slice(&[0, 128]);
+    blob.extend_from_slice(b"none");
+    blob.extend_from_slice(b"rght");
+    blob.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0]);
+    let file = file_with_blob(".", b"icvo", &blob);
+
+    let store = DsStore::new(&file).expect("Could not parse the store.");
+    let options = match *record(&store, "icvo") {
+        RecordValue::LegacyIconView(options) => options,
+        ref other => panic!("Unexpected value {:?}", other),
+    };
+    assert_eq!(options, LegacyIconViewOptions::Icv4 {
+        icon_size: 128,
+        arrangement: *b"none",
+        label_position: *b"rght",
+        unknown: [0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0],
+    });
+    assert_eq!(options.icon_size(), 128);
+    assert_eq!(options.arrangement(), b"none");
+    assert_eq!(options.label_position(), Some(b"rght"));
+    assert_eq!(store.to_bytes_lossless().expect("Could not write the store."), file);
+}
+
+#[test]
+fn icvo_with_wrong_signature_is_rejected() {
+    let mut blob = Vec::new();
+    blob.extend_from_slice(b"icv4");
+    blob.extend_from_slice(&[0; 14]);
+    let file = file_with_blob(".", b"icvo", &blob);
+    assert!(DsStore::new(&file).is_err());
+}
+
+#[test]
+fn fwi0_window_info() {
+    let mut blob = Vec::new();
+    blob.extend_from_slice(&[0, 80, 0xff, 0xf6, 1, 0x90, 2, 0x58]);
+    blob.extend_from_slice(b"Nlsv");
+    blob.extend_from_slice(&[0, 1, 0, 0]);
+    let file = file_with_blob(".", b"fwi0", &blob);
+
+    let store = DsStore::new(&file).expect("Could not parse the store.");
+    let info = match *record(&store, "fwi0") {
+        RecordValue::WindowInfo(info) => info,
+        ref other => panic!("Unexpected value {:?}", other),
+    };
+    assert_eq!(info, WindowInfo {top: 80, left: -10, bottom: 400, right: 600, view_style: *b"Nlsv", unknown: [0, 1, 0, 0]});
+    assert_eq!(info.style(), Some(StyleType::List));
+    assert_eq!(store.to_bytes_lossless().expect("Could not write the store."), file);
+}
+
+fn info_blob(len: usize) -> Vec<u8> {
+    let mut blob = Vec::new();
+    blob.extend_from_slice(&[0, 44, 0, 20, 1, 0x2c, 2, 0x30]);
+    blob.extend_from_slice(b"icnv");
+    blob.extend_from_slice(&[0; 4]);
+    blob.extend((0..len as u8 - 16).map(|i| i + 1));
+    blob
+}
+
+#[test]
+fn info_40_bytes() {
+    let file = file_with_blob(".", b"info", &info_blob(40));
+
+    let store = DsStore::new(&file).expect("Could not parse the store.");
+    let info = match *record(&store, "info") {
+        RecordValue::FinderInfo(info) => info,
+        ref other => panic!("Unexpected value {:?}", other),
+    };
+    assert_eq!(info.window, WindowInfo {top: 44, left: 20, bottom: 300, right: 560, view_style: *b"icnv", unknown: [0; 4]});
+    assert_eq!(info.unknown[0], 1);
+    assert_eq!(info.unknown[23], 24);
+    assert_eq!(info.extra, None);
+    assert_eq!(store.to_bytes_lossless().expect("Could not write the store."), file);
+}
+
+#[test]
+fn info_48_bytes() {
+    let file = file_with_blob(".", b"info", &info_blob(48));
+
+    let store = DsStore::new(&file).expect("Could not parse the store.");
+    let info = match *record(&store, "info") {
+        RecordValue::FinderInfo(info) => info,
+        ref other => panic!("Unexpected value {:?}", other),
+    };
+    assert_eq!(info.window.style(), Some(StyleType::Icon));
+    assert_eq!(info.extra, Some([25, 26, 27, 28, 29, 30, 31, 32]));
+    assert_eq!(store.to_bytes_lossless().expect("Could not write the store."), file);
+
+    let mut records = HashMap::new();
+    records.insert("info", RecordValue::FinderInfo(FinderInfo {extra: None, ..info}));
+    let mut contents = HashMap::new();
+    contents.insert(".".to_string(), records);
+    let file = DsStore::from_contents(contents).to_bytes().expect("Could not write the store.");
+    let store = DsStore::new(&file).expect("Could not parse the store.");
+    match *record(&store, "info") {
+        RecordValue::FinderInfo(read) => assert_eq!(read, FinderInfo {extra: None, ..info}),
+        ref other => panic!("Unexpected value {:?}", other),
+    }
+}
+
+#[test]
+fn info_with_wrong_size_is_rejected() {
+    let file = file_with_blob(".", b"info", &info_blob(44));
+    assert!(DsStore::new(&file).is_err());
+}
+
+#[test]
+fn lsvo_is_kept_as_is() {
+    let blob: Vec<u8> = (0..76).collect();
+    let file = file_with_blob(".", b"lsvo", &blob);
+
+    let store = DsStore::new(&file).expect("Could not parse the store.");
+    match *record(&store, "lsvo") {
+        RecordValue::Slice(data) => assert_eq!(data, &blob[..]),
+        ref other => panic!("Unexpected value {:?}", other),
+    }
+    assert_eq!(store.to_bytes_lossless().expect("Could not write the store."), file);
+}
+
+#[test]
+fn new_legacy_records() {
+    let window = WindowInfo {top: 44, left: 0, bottom: 500, right: 700, view_style: *b"icnv", unknown: [0; 4]};
+    let options = LegacyIconViewOptions::Icvo {unknown: [0; 8], icon_size: 32, arrangement: *b"none"};
+    let mut records = HashMap::new();
+    records.insert("fwi0", RecordValue::WindowInfo(window));
+    records.insert("icvo", RecordValue::LegacyIconView(options));
+    let mut contents = HashMap::new();
+    contents.insert(".".to_string(), records);
+    let file = DsStore::from_contents(contents).to_bytes().expect("Could not write the store.");
+
+    let store = DsStore::new(&file).expect("Could not parse the store.");
+    match *record(&store, "fwi0") {
+        RecordValue::WindowInfo(read) => assert_eq!(read, window),
+        ref other => panic!("Unexpected value {:?}", other),
+    }
+    match *record(&store, "icvo") {
+        RecordValue::LegacyIconView(read) => assert_eq!(read, options),
+        ref other => panic!("Unexpected value {:?}", other),
+    }
+}
+
+fn upgrade(file_name: &str, from: &'static str, to: &'static str, outcome: UpgradeOutcome) -> LegacyUpgrade {
+    LegacyUpgrade {file_name: file_name.to_string(), from, to, outcome}
+}
+
+#[test]
+fn upgrade_legacy_views() {
+    let window = WindowInfo {top: 100, left: 50, bottom: 500, right: 750, view_style: *b"clmv", unknown: [0; 4]};
+    let icv4 = LegacyIconViewOptions::Icv4 {icon_size: 64, arrangement: *b"grid", label_position: *b"rght", unknown: [0; 12]};
+    let icvo = LegacyIconViewOptions::Icvo {unknown: [0; 8], icon_size: 32, arrangement: *b"none"};
+
+    let mut root = HashMap::new();
+    root.insert("fwi0", RecordValue::WindowInfo(window));
+    root.insert("icvo", RecordValue::LegacyIconView(icv4));
+    let mut sub = HashMap::new();
+    sub.insert("icvo", RecordValue::LegacyIconView(icvo));
+    let mut contents = HashMap::new();
+    contents.insert(".".to_string(), root);
+    contents.insert("sub".to_string(), sub);
+    let mut store = DsStore::from_contents(contents);
+
+    let modern = IconViewOptions {icon_size: Some(16.0), ..IconViewOptions::default()};
+    store.set_icon_view_options("sub", &modern);
+
+    let mut upgrades = store.upgrade_legacy_views();
+    upgrades.sort_by(|l, r| (&l.file_name, l.to).cmp(&(&r.file_name, r.to)));
+    assert_eq!(upgrades, vec![
+        upgrade(".", "icvo", "icvp", UpgradeOutcome::Added),
+        upgrade(".", "fwi0", "vstl", UpgradeOutcome::Added),
+        upgrade("sub", "icvo", "icvp", UpgradeOutcome::ModernRecordKept),
+    ]);
+
+    // The window rectangle is left out, so there is nothing to put in "bwsp".
+    assert!(store.browser_window_settings(".").is_none());
+    let options = store.icon_view_options(".").expect("No icvp record.");
+    assert_eq!(options.icon_size, Some(64.0));
+    assert_eq!(options.arrange_by, Some(Arrangement::Grid));
+    assert_eq!(options.label_position, Some(LabelPosition::Right));
+    match *record(&store, "vstl") {
+        RecordValue::Style(style) => assert_eq!(style, StyleType::ColumnBrowser),
+        ref other => panic!("Unexpected value {:?}", other),
+    }
+    assert_eq!(store.icon_view_options("sub"), Some(modern));
+    assert!(store.contents()["."].contains_key("icvo"));
+
+    // Once upgraded, nothing is left to add.
+    assert!(store.upgrade_legacy_views().iter().all(|u| u.outcome == UpgradeOutcome::ModernRecordKept));
+    store.to_bytes().expect("Could not write the store.");
+}
+
+#[test]
+fn upgrade_list_view_text_size() {
+    let lsvo: Vec<u8> = (0..76).collect();
+    let mut root = HashMap::new();
+    root.insert("lsvo", RecordValue::Slice(&lsvo));
+    root.insert("lsvt", RecordValue::I16(12));
+    let mut sub = HashMap::new();
+    sub.insert("lsvt", RecordValue::I16(10));
+    let mut only_lsvo = HashMap::new();
+    only_lsvo.insert("lsvo", RecordValue::Slice(&lsvo));
+    let mut contents = HashMap::new();
+    contents.insert(".".to_string(), root);
+    contents.insert("sub".to_string(), sub);
+    contents.insert("other".to_string(), only_lsvo);
+    let mut store = DsStore::from_contents(contents);
+
+    let modern = ListViewSettings {sort_column: Some("name".to_string()), ..ListViewSettings::default()};
+    store.set_list_view_settings("sub", "lsvP", &modern).unwrap();
+
+    assert_eq!(store.upgrade_legacy_views(), vec![
+        upgrade(".", "lsvt", "lsvp", UpgradeOutcome::Added),
+        upgrade("sub", "lsvt", "lsvp", UpgradeOutcome::ModernRecordKept),
+    ]);
+    let settings = store.list_view_settings(".", "lsvp").expect("No lsvp record.");
+    assert_eq!(settings, ListViewSettings {text_size: Some(12.0), ..ListViewSettings::default()});
+    assert!(store.contents()["."].contains_key("lsvo"));
+    assert_eq!(store.list_view_settings("sub", "lsvp"), None);
+    assert_eq!(store.list_view_settings("sub", "lsvP"), Some(modern));
+    assert_eq!(store.contents()["other"].len(), 1);
+
+    let file = store.to_bytes().expect("Could not write the store.");
+    let reread = DsStore::new(&file).expect("Could not parse the store.");
+    assert_eq!(reread.list_view_settings(".", "lsvp").and_then(|s| s.text_size), Some(12.0));
+}
@@ -0,0 +1,78 @@
+extern crate ds_store;
+
+mod common;
+
+use ds_store::{Alias, DsStore, IconLocation, RecordValue};
+use ds_store::allocator::{Allocator, BackgroundType};
+use common::{deep_store, file_name, read_example};
+
+#[test]
+fn finds_single_records() {
+    let file = deep_store();
+    let allocator = Allocator::new(&file).expect("Could not read the allocator.");
+    for i in (0..1500).step_by(7) {
+        match allocator.find_record(&file_name(i), "fwsw").expect("Could not look up the record.") {
+            Some(RecordValue::I32(n)) => assert_eq!(n, i as i32),
+            other => panic!("Unexpected value {:?}", other),
+        }
+        match allocator.find_record(&file_name(i), "cmmt").expect("Could not look up the record.") {
+            Some(RecordValue::String(ref s)) => assert_eq!(s, &format!("comment {}", i)),
+            other => panic!("Unexpected value {:?}", other),
+        }
+    }
+    // Names compare case-insensitively, as in the B-Tree.
+    assert!(allocator.find_record(&file_name(3).to_uppercase(), "fwsw").unwrap().is_some());
+
+    assert!(allocator.find_record(&file_name(1500), "fwsw").unwrap().is_none());
+    assert!(allocator.find_record(&file_name(10), "Iloc").unwrap().is_none());
+    assert!(allocator.find_record("", "fwsw").unwrap().is_none());
+    assert!(allocator.find_record("zzz", "fwsw").unwrap().is_none());
+}
+
+#[test]
+fn finds_all_records_of_a_file() {
+    let file = deep_store();
+    let allocator = Allocator::new(&file).expect("Could not read the allocator.");
+    for i in (0..1500).step_by(11) {
+        let records = allocator.find_records(&file_name(i)).expect("Could not look up the records.");
+        let codes: Vec<&str> = records.iter().map(|&(code, _)| code).collect();
+        assert_eq!(codes, vec!["cmmt", "fwsw"]);
+    }
+    assert!(allocator.find_records("00001").unwrap().is_empty());
+}
+
+#[test]
+fn matches_a_full_traversal() {
+    let file = read_example();
+    let store = DsStore::new(&file).expect("Could not parse the store.");
+    let allocator = Allocator::new(&file).expect("Could not read the allocator.");
+    for (file_name, records) in store.contents() {
+        let found = allocator.find_records(file_name).expect("Could not look up the records.");
+        assert_eq!(found.len(), records.len());
+        for (code, value) in found {
+            assert_eq!(format!("{:?}", value), format!("{:?}", records[code]));
+            let single = allocator.find_record(file_name, code).expect("Could not look up the record.");
+            assert_eq!(format!("{:?}", single), format!("{:?}", Some(&records[code])));
+        }
+    }
+}
+
+#[test]
+fn finds_picture_backgrounds() {
+    let mut store = DsStore::default();
+    let alias = Alias::new("Installer", ".background/bg.png", None, None);
+    store.set_background_picture(".", alias.clone());
+    store.set_icon_location("app", 100, 200);
+    let file = store.to_bytes().expect("Could not write the store.");
+
+    let allocator = Allocator::new(&file).expect("Could not read the allocator.");
+    let records = allocator.find_records(".").expect("Could not look up the records.");
+    match records.iter().find(|&&(code, _)| code == "BKGD") {
+        Some(&(_, RecordValue::Background(BackgroundType::Picture(_, Some(ref read))))) => assert_eq!(read, &alias),
+        other => panic!("Unexpected value {:?}", other),
+    }
+    match allocator.find_record("app", "Iloc").expect("Could not look up the record.") {
+        Some(RecordValue::IconLocation(location)) => assert_eq!(location, IconLocation::new(100, 200)),
+        other => panic!("Unexpected value {:?}", other),
+    }
+}
@@ -0,0 +1,59 @@
+extern crate ds_store;
+
+mod common;
+
+use ds_store::{DsStore, RecordValue};
+use ds_store::allocator::StyleType;
+use common::{read_example, unsorted_store};
+
+#[test]
+fn records_are_in_b_tree_order() {
+    let mut store = DsStore::default();
+    for name in &["b", "A", "C"] {
+        store.set_record(name, "vstl", RecordValue::Style(StyleType::Icon)).expect("Could not set the record.");
+        store.set_icon_location(name, 10, 20);
+    }
+    let keys: Vec<(&str, &str)> = store.records().into_iter().map(|(name, code, _)| (name, code)).collect();
+    // Filenames compare case-insensitively and codes case-sensitively, as in the B-tree.
+    assert_eq!(keys, vec![("A", "Iloc"), ("A", "vstl"), ("b", "Iloc"), ("b", "vstl"), ("C", "Iloc"), ("C", "vstl")]);
+}
+
+#[test]
+fn order_is_stable() {
+    let data = read_example();
+    let store = DsStore::new(&data).expect("Could not parse the store.");
+    let first: Vec<(String, String)> = store.records().into_iter().map(|(name, code, _)| (name.to_string(), code.to_string())).collect();
+    assert_eq!(first.len(), store.contents().values().map(|records| records.len()).sum::<usize>());
+
+    let written = store.to_bytes().expect("Could not write the store.");
+    let reread = DsStore::new(&written).expect("Could not parse the written store.");
+    let second: Vec<(String, String)> = reread.records().into_iter().map(|(name, code, _)| (name.to_string(), code.to_string())).collect();
+    assert_eq!(first, second);
+
+    let names: Vec<&String> = store.contents().keys().collect();
+    let mut sorted = names.clone();
+    sorted.sort();
+    assert_eq!(names, sorted);
+}
+
+#[test]
+fn stored_order_is_kept() {
+    let file = unsorted_store();
+    let mut store = DsStore::new(&file).expect("Could not parse the store.");
+    let names = |store: &DsStore| -> Vec<String> { store.records().into_iter().map(|(name, _, _)| name.to_string()).collect() };
+    assert_eq!(names(&store), vec!["b", "a"]);
+
+    // Changed records stay where they are, and added records go where `to_bytes` would put them.
+    store.set_record("a", "cmmt", RecordValue::String("changed".to_string())).unwrap();
+    store.set_record("c", "cmmt", RecordValue::String("added".to_string())).unwrap();
+    store.set_record("0", "cmmt", RecordValue::String("added".to_string())).unwrap();
+    assert_eq!(names(&store), vec!["0", "b", "a", "c"]);
+    store.remove_file("b");
+    assert_eq!(names(&store), vec!["0", "a", "c"]);
+
+    // Written stores are sorted.
+    let written = store.to_bytes().expect("Could not write the store.");
+    assert_eq!(names(&DsStore::new(&written).expect("Could not parse the store.")), vec!["0", "a", "c"]);
+    store.rename_file("0", "d");
+    assert_eq!(names(&store), vec!["a", "c", "d"]);
+}
@@ -0,0 +1,187 @@
+extern crate ds_store;
+
+use ds_store::{DsStore, Error, Plist, PlistRecord, RecordValue};
+use ds_store::plist;
+
+/// A dictionary written by Python's `plistlib`, using every object type Finder writes.
+const EVERY_TYPE: [u8; 284] = [
+    0x62, 0x70, 0x6c, 0x69, 0x73, 0x74, 0x30, 0x30, 0xdc, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07,
+    0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17,
+    0x18, 0x59, 0x61, 0x72, 0x72, 0x61, 0x6e, 0x67, 0x65, 0x42, 0x79, 0x58, 0x69, 0x63, 0x6f, 0x6e,
+    0x53, 0x69, 0x7a, 0x65, 0x5d, 0x6c, 0x61, 0x62, 0x65, 0x6c, 0x4f, 0x6e, 0x42, 0x6f, 0x74, 0x74,
+    0x6f, 0x6d, 0x5f, 0x10, 0x0f, 0x73, 0x68, 0x6f, 0x77, 0x49, 0x63, 0x6f, 0x6e, 0x50, 0x72, 0x65,
+    0x76, 0x69, 0x65, 0x77, 0x5f, 0x10, 0x12, 0x76, 0x69, 0x65, 0x77, 0x4f, 0x70, 0x74, 0x69, 0x6f,
+    0x6e, 0x73, 0x56, 0x65, 0x72, 0x73, 0x69, 0x6f, 0x6e, 0x54, 0x6e, 0x61, 0x6d, 0x65, 0x54, 0x64,
+    0x61, 0x74, 0x61, 0x53, 0x62, 0x69, 0x67, 0x55, 0x6c, 0x61, 0x72, 0x67, 0x65, 0x54, 0x77, 0x68,
+    0x65, 0x6e, 0x53, 0x75, 0x69, 0x64, 0x54, 0x6c, 0x69, 0x73, 0x74, 0x54, 0x6e, 0x6f, 0x6e, 0x65,
+    0x23, 0x40, 0x50, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x09, 0x08, 0x10, 0x01, 0x69, 0x00, 0xdc,
+    0x00, 0x6e, 0x00, 0xef, 0x00, 0x63, 0x00, 0xf8, 0x00, 0x64, 0x00, 0xe9, 0x00, 0x20, 0x26, 0x03,
+    0x43, 0x00, 0x01, 0x02, 0x13, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfb, 0x12, 0x00, 0x01,
+    0x11, 0x70, 0x33, 0x41, 0xc1, 0xde, 0x0c, 0x40, 0x00, 0x00, 0x00, 0x80, 0x07, 0xa3, 0x11, 0x19,
+    0x1a, 0x53, 0x74, 0x77, 0x6f, 0xa1, 0x1b, 0x23, 0x40, 0x0c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x08, 0x21, 0x2b, 0x34, 0x42, 0x54, 0x69, 0x6e, 0x73, 0x77, 0x7d, 0x82, 0x86, 0x8b, 0x90, 0x99,
+    0x9a, 0x9b, 0x9d, 0xb0, 0xb4, 0xbd, 0xc2, 0xcb, 0xcd, 0xd1, 0xd5, 0xd7, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1c, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xe0,
+];
+
+fn every_type() -> Plist {
+    Plist::Dictionary(vec![
+        ("arrangeBy".to_string(), Plist::String("none".to_string())),
+        ("iconSize".to_string(), Plist::Real(64.0)),
+        ("labelOnBottom".to_string(), Plist::Boolean(true)),
+        ("showIconPreview".to_string(), Plist::Boolean(false)),
+        ("viewOptionsVersion".to_string(), Plist::Integer(1)),
+        ("name".to_string(), Plist::String("Ünïcødé ☃".to_string())),
+        ("data".to_string(), Plist::Data(vec![0, 1, 2])),
+        ("big".to_string(), Plist::Integer(-5)),
+        ("large".to_string(), Plist::Integer(70000)),
+        ("when".to_string(), Plist::Date(599529600.0)),
+        ("uid".to_string(), Plist::Uid(7)),
+        ("list".to_string(), Plist::Array(vec![
+            Plist::Integer(1),
+            Plist::String("two".to_string()),
+            Plist::Array(vec![Plist::Real(3.5)]),
+        ])),
+    ])
+}
+
+#[test]
+fn parses_every_type() {
+    let value = plist::parse(&EVERY_TYPE).expect("Could not parse the plist.");
+    assert_eq!(value, every_type());
+    assert_eq!(value.get("iconSize").and_then(Plist::as_real), Some(64.0));
+    assert_eq!(value.get("arrangeBy").and_then(Plist::as_str), Some("none"));
+    assert_eq!(value.get("missing"), None);
+}
+
+#[test]
+fn rejects_bad_data() {
+    assert!(plist::parse(b"bplist00").is_err());
+    assert!(plist::parse(&EVERY_TYPE[..EVERY_TYPE.len() - 1]).is_err());
+
+    // An array containing itself.
+    let mut cycle = b"bplist00\xa1\x00\x08".to_vec();
+    cycle.extend_from_slice(&[0, 0, 0, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 0, 0, 1]);
+    cycle.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 10]);
+    assert!(plist::parse(&cycle).is_err());
+}
+
+#[test]
+fn plist_records_in_a_store() {
+    let mut store = DsStore::default();
+    let record = PlistRecord::parse(&EVERY_TYPE).expect("Could not parse the plist.");
+    store.set_record(".", "icvp", RecordValue::Plist(record)).unwrap();
+    let written = store.to_bytes().expect("Could not write the DS_Store.");
+
+    let reread = DsStore::new(&written).expect("Could not construct the DS_Store.");
+    match reread.contents()["."]["icvp"] {
+        RecordValue::Plist(ref record) => {
+            assert_eq!(record.value(), &every_type());
+            assert_eq!(record.source(), Some(&EVERY_TYPE[..]));
+        },
+        ref other => panic!("Unexpected value {:?}", other),
+    }
+}
+
+#[test]
+fn writes_like_apple() {
+    assert_eq!(plist::write(&every_type()), &EVERY_TYPE[..]);
+}
+
+#[test]
+fn write_then_parse() {
+    // Enough objects to need 2-byte references, and long containers and strings.
+    let many: Vec<Plist> = (0..300).map(|i| Plist::String(format!("entry number {}", i))).collect();
+    let value = Plist::Dictionary(vec![
+        ("many".to_string(), Plist::Array(many)),
+        ("long data".to_string(), Plist::Data(vec![7; 1000])),
+        ("negative".to_string(), Plist::Integer(-1)),
+        ("huge".to_string(), Plist::Integer(1 << 40)),
+        ("nested".to_string(), every_type()),
+    ]);
+    let written = plist::write(&value);
+    assert_eq!(plist::parse(&written).expect("Could not parse the written plist."), value);
+}
+
+#[test]
+fn new_plist_records_in_a_store() {
+    let mut store = DsStore::default();
+    store.set_record(".", "bwsp", RecordValue::Plist(PlistRecord::new(every_type()))).unwrap();
+    let written = store.to_bytes().expect("Could not write the DS_Store.");
+
+    let reread = DsStore::new(&written).expect("Could not construct the DS_Store.");
+    match reread.contents()["."]["bwsp"] {
+        RecordValue::Plist(ref record) => assert_eq!(record.value(), &every_type()),
+        ref other => panic!("Unexpected value {:?}", other),
+    }
+}
+
+#[test]
+fn corrupt_plist_records_are_kept_as_blobs() {
+    let mut store = DsStore::default();
+    store.set_record(".", "bwsp", RecordValue::Slice(b"bplist00 but not really")).unwrap();
+    store.set_record(".", "icvp", RecordValue::Plist(PlistRecord::new(every_type()))).unwrap();
+    store.set_record(".", "cmmt", RecordValue::String("comment".to_string())).unwrap();
+    let written = store.to_bytes().expect("Could not write the DS_Store.");
+
+    let reread = DsStore::new(&written).expect("Could not construct the DS_Store.");
+    match reread.contents()["."]["bwsp"] {
+        RecordValue::Slice(blob) => assert_eq!(blob, b"bplist00 but not really"),
+        ref other => panic!("Unexpected value {:?}", other),
+    }
+    assert!(reread.browser_window_settings(".").is_none());
+    match reread.contents()["."]["icvp"] {
+        RecordValue::Plist(ref record) => assert_eq!(record.value(), &every_type()),
+        ref other => panic!("Unexpected value {:?}", other),
+    }
+    match reread.contents()["."]["cmmt"] {
+        RecordValue::String(ref s) => assert_eq!(s, "comment"),
+        ref other => panic!("Unexpected value {:?}", other),
+    }
+    assert_eq!(reread.to_bytes_lossless().expect("Could not write the DS_Store."), written);
+}
+
+/// A property list of `levels` nested arrays, where each array holds the next one twice,
+/// so that it expands to 2^`levels` values.
+fn shared_arrays(levels: u8) -> Vec<u8> {
+    let mut data = b"bplist00".to_vec();
+    let mut offsets = Vec::new();
+    for i in 0..levels {
+        offsets.push(data.len() as u8);
+        data.extend_from_slice(&[0xa2, i + 1, i + 1]);
+    }
+    offsets.push(data.len() as u8);
+    data.push(0x08);
+    let offset_table = data.len() as u64;
+    data.extend_from_slice(&offsets);
+    data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 1, 1]);
+    data.extend_from_slice(&(offsets.len() as u64).to_be_bytes());
+    data.extend_from_slice(&0u64.to_be_bytes());
+    data.extend_from_slice(&offset_table.to_be_bytes());
+    data
+}
+
+#[test]
+fn shared_arrays_are_not_expanded_without_end() {
+    let mut expected = Plist::Boolean(false);
+    for _ in 0..3 {
+        expected = Plist::Array(vec![expected.clone(), expected]);
+    }
+    assert_eq!(plist::parse(&shared_arrays(3)).expect("Could not parse the plist."), expected);
+
+    // This would be 2^40 values.
+    let data = shared_arrays(40);
+    match plist::parse(&data) {
+        Err(Error::BadData(_)) => {},
+        other => panic!("Unexpected result {:?}", other.map(|_| ())),
+    }
+    let mut store = DsStore::default();
+    store.set_record(".", "bwsp", RecordValue::Slice(&data)).unwrap();
+    let written = store.to_bytes().expect("Could not write the DS_Store.");
+    let reread = DsStore::new(&written).expect("Could not construct the DS_Store.");
+    match reread.contents()["."]["bwsp"] {
+        RecordValue::Slice(blob) => assert_eq!(blob, &data[..]),
+        ref other => panic!("Unexpected value {:?}", other),
+    }
+}
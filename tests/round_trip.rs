@@ -0,0 +1,89 @@
+extern crate ds_store;
+
+mod common;
+
+use ds_store::{BackgroundType, DsStore, GenericValue, IconLocation, RecordValue};
+use common::read_example;
+
+#[test]
+fn unmodified_store_is_byte_exact() {
+    let buf = read_example();
+    let store = DsStore::new(&buf).expect("Could not construct the DS_Store.");
+    let written = store.to_bytes_lossless().expect("Could not write the DS_Store.");
+    assert_eq!(buf, written);
+}
+
+#[test]
+fn modified_value_keeps_layout() {
+    let buf = read_example();
+    let mut store = DsStore::new(&buf).expect("Could not construct the DS_Store.");
+    store.set_icon_location("src", 1, 2);
+    let written = store.to_bytes_lossless().expect("Could not write the DS_Store.");
+
+    assert_eq!(buf.len(), written.len());
+    let differing: Vec<usize> = (0..buf.len()).filter(|&i| buf[i] != written[i]).collect();
+    assert_eq!(differing.len(), 2);
+
+    let reread = DsStore::new(&written).expect("Could not construct the written DS_Store.");
+    assert_eq!(reread.icon_location("src"), Some(IconLocation::new(1, 2)));
+}
+
+/// Overwrites the bytes found `skip` bytes after `pattern` in `file` with `value`.
+fn patch(file: &mut [u8], pattern: &[u8], skip: usize, value: &[u8]) {
+    let position = file.windows(pattern.len()).position(|window| window == pattern).expect("Pattern not found.");
+    let start = position + pattern.len() + skip;
+    file[start..start + value.len()].copy_from_slice(value);
+}
+
+#[test]
+fn discarded_bytes_are_kept() {
+    let mut store = DsStore::default();
+    store.set_record("a", "BKGD", RecordValue::Background(BackgroundType::SolidColor(1, 2, 3))).unwrap();
+    store.set_record("a", "icvt", RecordValue::I16(12)).unwrap();
+    store.set_record("a", "ICVO", RecordValue::Bool(true)).unwrap();
+    store.set_record("a", "zzzz", RecordValue::Unknown("zzzz", GenericValue::Short(-1))).unwrap();
+    store.set_record("b", "BKGD", RecordValue::Background(BackgroundType::Default)).unwrap();
+    store.set_record("b", "ICVO", RecordValue::Bool(false)).unwrap();
+    store.set_record("c", "BKGD", RecordValue::Background(BackgroundType::Picture(100, None))).unwrap();
+    let mut file = store.to_bytes().expect("Could not write the DS_Store.");
+
+    // Nonzero padding, upper halves of "shor" values, and a "bool" that is neither 0 nor 1.
+    patch(&mut file, b"BKGDblob\x00\x00\x00\x0cClrB", 6, &[0xab, 0xcd]);
+    patch(&mut file, b"BKGDblob\x00\x00\x00\x0cDefB", 0, &[1, 2, 3, 4, 5, 6, 7, 8]);
+    patch(&mut file, b"BKGDblob\x00\x00\x00\x0cPctB", 4, &[9, 10, 11, 12]);
+    patch(&mut file, b"icvtshor", 0, &[0x12, 0x34]);
+    patch(&mut file, b"zzzzshor", 0, &[0x56, 0x78]);
+    patch(&mut file, b"\x00bICVObool", 0, &[2]);
+
+    let store = DsStore::new(&file).expect("Could not construct the DS_Store.");
+    assert_eq!(store.to_bytes_lossless().expect("Could not write the DS_Store."), file);
+
+    // Changed values are written as they are.
+    let mut store = DsStore::new(&file).expect("Could not construct the DS_Store.");
+    store.set_record("a", "icvt", RecordValue::I16(13)).unwrap();
+    store.set_record("b", "ICVO", RecordValue::Bool(true)).unwrap();
+    let written = store.to_bytes_lossless().expect("Could not write the DS_Store.");
+    let differing: Vec<usize> = (0..file.len()).filter(|&i| file[i] != written[i]).collect();
+    assert_eq!(differing.len(), 4);
+    let reread = DsStore::new(&written).expect("Could not construct the written DS_Store.");
+    assert!(matches!(reread.contents()["a"]["icvt"], RecordValue::I16(13)));
+    assert!(matches!(reread.contents()["b"]["ICVO"], RecordValue::Bool(true)));
+}
+
+#[test]
+fn added_record_is_rejected() {
+    let buf = read_example();
+    let mut store = DsStore::new(&buf).expect("Could not construct the DS_Store.");
+    store.set_record("src", "cmmt", RecordValue::String("comment".to_string())).unwrap();
+    assert!(store.to_bytes_lossless().is_err());
+    assert!(store.to_bytes().is_ok());
+}
+
+#[test]
+fn icon_locations() {
+    let buf = read_example();
+    let store = DsStore::new(&buf).expect("Could not construct the DS_Store.");
+    assert_eq!(store.icon_location(".git"), Some(IconLocation::new(59, 40)));
+    assert_eq!(store.icon_location("target"), Some(IconLocation::new(274, 155)));
+    assert_eq!(store.icon_location("nonexistent"), None);
+}
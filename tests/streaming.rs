@@ -0,0 +1,60 @@
+extern crate ds_store;
+
+mod common;
+
+use ds_store::{DsStore, RecordValue};
+use ds_store::allocator::Allocator;
+use common::{deep_store, file_name, read_example, unsorted_store};
+
+fn assert_same_order(file: &[u8]) {
+    let store = DsStore::new(file).expect("Could not parse the store.");
+    let expected: Vec<(String, String, String)> = store.records().into_iter()
+        .map(|(name, code, value)| (name.to_string(), code.to_string(), format!("{:?}", value)))
+        .collect();
+
+    let allocator = Allocator::new(file).expect("Could not read the allocator.");
+    let streamed: Vec<(String, String, String)> = allocator.records()
+        .map(|record| record.expect("Could not read a record."))
+        .map(|(name, code, value)| (name, code.to_string(), format!("{:?}", value)))
+        .collect();
+    assert_eq!(streamed, expected);
+}
+
+#[test]
+fn streams_in_b_tree_order() {
+    assert_same_order(&deep_store());
+    assert_same_order(&read_example());
+    assert_same_order(&DsStore::default().to_bytes().expect("Could not write the store."));
+    // Not in the order this crate writes records in.
+    assert_same_order(&unsorted_store());
+}
+
+#[test]
+fn stops_early() {
+    let file = deep_store();
+    let allocator = Allocator::new(&file).expect("Could not read the allocator.");
+    let found = allocator.records()
+        .map(|record| record.expect("Could not read a record."))
+        .find(|&(_, code, ref value)| code == "fwsw" && matches!(*value, RecordValue::I32(42)));
+    assert_eq!(found.map(|(name, _, _)| name), Some(file_name(42)));
+}
+
+#[test]
+fn stops_at_the_first_error() {
+    let mut file = deep_store();
+    let mut pattern: Vec<u8> = file_name(700).encode_utf16().flat_map(|unit| unit.to_be_bytes().to_vec()).collect();
+    pattern.extend_from_slice(b"fwswlong");
+    let position = file.windows(pattern.len()).position(|window| window == &pattern[..]).expect("No record to corrupt.");
+    let data_type = position + pattern.len() - 4;
+    file[data_type..data_type + 4].copy_from_slice(b"xxxx");
+
+    let allocator = Allocator::new(&file).expect("Could not read the allocator.");
+    let mut records = allocator.records();
+    // Every record before the corrupted one, including the other record of its file.
+    for i in 0..1401 {
+        let (name, code, _) = records.next().expect("Stopped too early.").expect("Could not read a record.");
+        assert_eq!((name, code), (file_name(i / 2), if i % 2 == 0 { "cmmt" } else { "fwsw" }));
+    }
+    assert!(records.next().expect("No error.").is_err());
+    assert!(records.next().is_none());
+}
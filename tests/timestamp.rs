@@ -0,0 +1,60 @@
+extern crate chrono;
+extern crate ds_store;
+
+use chrono::{TimeZone, Utc};
+use ds_store::{DsStore, RecordValue, Timestamp};
+
+#[test]
+fn epochs() {
+    let mac_epoch = Timestamp::from_ticks(0);
+    assert_eq!(mac_epoch.to_date_time(), Some(Utc.with_ymd_and_hms(1904, 1, 1, 0, 0, 0).unwrap()));
+
+    let unix_epoch = Timestamp::from_ticks(2082844800 << 16);
+    assert_eq!(unix_epoch.unix_seconds(), 0);
+    assert_eq!(unix_epoch.fraction(), 0);
+    assert_eq!(unix_epoch.to_date_time(), Some(Utc.timestamp_opt(0, 0).unwrap()));
+}
+
+#[test]
+fn sub_second_fraction() {
+    // 2016-08-24 12:34:56.5 UTC, as Finder stores it.
+    let timestamp = Timestamp::from_ticks(0x0000_d3e3_44f0_8000);
+    assert_eq!(timestamp.fraction(), 0x8000);
+    let expected = Utc.with_ymd_and_hms(2016, 8, 24, 12, 34, 56).unwrap() + chrono::Duration::milliseconds(500);
+    assert_eq!(timestamp.to_date_time(), Some(expected));
+    assert_eq!(Timestamp::from_date_time(&expected), timestamp);
+}
+
+#[test]
+fn conversion_keeps_every_tick() {
+    for &ticks in &[1, 0x7fff, 0xffff, 0x0000_d3e3_44f0_0001, 0x0000_d3e3_44f0_ffff, -1] {
+        let timestamp = Timestamp::from_ticks(ticks);
+        let date_time = timestamp.to_date_time().unwrap();
+        assert_eq!(Timestamp::from_date_time(&date_time), timestamp);
+    }
+}
+
+#[test]
+fn round_trip_through_file() {
+    let modified = Timestamp::from_ticks(0x0000_d3e3_44f0_1234);
+    let mut store = DsStore::default();
+    // "logS" and "phyS" sort around "modD", so all three 8-byte values are read in a row.
+    store.set_record("file", "logS", RecordValue::I64(1 << 40)).unwrap();
+    store.set_record("file", "modD", RecordValue::DateTime(modified)).unwrap();
+    store.set_record("file", "phyS", RecordValue::I64(4096)).unwrap();
+    let written = store.to_bytes().unwrap();
+
+    let reread = DsStore::new(&written).unwrap();
+    let metadata = &reread.contents()["file"];
+    match metadata["modD"] {
+        RecordValue::DateTime(t) => assert_eq!(t, modified),
+        ref other => panic!("Unexpected value {:?}", other),
+    }
+    match (&metadata["logS"], &metadata["phyS"]) {
+        (&RecordValue::I64(logical), &RecordValue::I64(physical)) => {
+            assert_eq!(logical, 1 << 40);
+            assert_eq!(physical, 4096);
+        },
+        other => panic!("Unexpected values {:?}", other),
+    }
+}
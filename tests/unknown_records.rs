@@ -0,0 +1,82 @@
+extern crate ds_store;
+
+use ds_store::{DsStore, Error, GenericValue, RecordValue, Timestamp};
+
+/// Writes a store with a single unknown "zzzz" record, checks that its value is stored as `encoded`,
+/// and that reading it back gives the same value, which is written back as the same bytes.
+fn check_round_trip(value: GenericValue, encoded: &[u8]) {
+    let mut store = DsStore::default();
+    store.set_record("file", "zzzz", RecordValue::Unknown("zzzz", value.clone())).unwrap();
+    let file = store.to_bytes().expect("Could not write the store.");
+    let mut record = b"zzzz".to_vec();
+    record.extend_from_slice(encoded);
+    assert!(file.windows(record.len()).any(|window| window == &record[..]), "{:?} is not stored as {:?}", value, encoded);
+
+    let read = DsStore::new(&file).expect("Could not read the store.");
+    match read.contents()["file"]["zzzz"] {
+        RecordValue::Unknown(code, ref read_value) => {
+            assert_eq!(code, "zzzz");
+            assert_eq!(format!("{:?}", read_value), format!("{:?}", value));
+        },
+        ref other => panic!("Unexpected value {:?}", other),
+    }
+    assert_eq!(read.to_bytes().expect("Could not write the store."), file);
+    assert_eq!(read.to_bytes_lossless().expect("Could not write the store."), file);
+}
+
+#[test]
+fn long_values() {
+    check_round_trip(GenericValue::Long(-2), b"long\xff\xff\xff\xfe");
+}
+
+#[test]
+fn shor_values() {
+    check_round_trip(GenericValue::Short(-2), b"shor\x00\x00\xff\xfe");
+}
+
+#[test]
+fn bool_values() {
+    check_round_trip(GenericValue::Bool(true), b"bool\x01");
+    check_round_trip(GenericValue::Bool(false), b"bool\x00");
+}
+
+#[test]
+fn blob_values() {
+    check_round_trip(GenericValue::Blob(b"abc"), b"blob\x00\x00\x00\x03abc");
+    check_round_trip(GenericValue::Blob(b""), b"blob\x00\x00\x00\x00");
+}
+
+#[test]
+fn type_values() {
+    check_round_trip(GenericValue::Type(b"icnv"), b"typeicnv");
+}
+
+#[test]
+fn ustr_values() {
+    check_round_trip(GenericValue::UStr("h\u{e9}".to_string()), b"ustr\x00\x00\x00\x02\x00h\x00\xe9");
+}
+
+#[test]
+fn comp_values() {
+    check_round_trip(GenericValue::Comp(0x0102_0304_0506_0708), b"comp\x01\x02\x03\x04\x05\x06\x07\x08");
+}
+
+#[test]
+fn dutc_values() {
+    check_round_trip(GenericValue::DateTime(Timestamp::from_ticks(0x10000)), b"dutc\x00\x00\x00\x00\x00\x01\x00\x00");
+}
+
+#[test]
+fn unknown_data_type_is_rejected() {
+    let mut store = DsStore::default();
+    store.set_record("file", "zzzz", RecordValue::Unknown("zzzz", GenericValue::Long(1))).unwrap();
+    let mut file = store.to_bytes().expect("Could not write the store.");
+    let position = file.windows(8).position(|window| window == b"zzzzlong").expect("No record to corrupt.");
+    file[position + 4..position + 8].copy_from_slice(b"xxxx");
+
+    match DsStore::new(&file) {
+        Err(Error::UnkonwnStructureType(data_type)) => assert_eq!(data_type, b"xxxx"),
+        Err(other) => panic!("Unexpected error {:?}", other),
+        Ok(_) => panic!("The unknown data type was accepted."),
+    }
+}
@@ -0,0 +1,291 @@
+extern crate ds_store;
+
+use ds_store::{Arrangement, BrowserWindowSettings, Column, ColumnLayout, DsStore, IconViewBackground, IconViewOptions,
+               LabelPosition, ListViewSettings, Plist, Rect};
+
+fn entry(key: &str, value: Plist) -> (String, Plist) {
+    (key.to_string(), value)
+}
+
+#[test]
+fn rect_forms() {
+    let rect = Rect::parse("{{200, 120}, {920, 436}}").expect("Could not parse the rect.");
+    assert_eq!(rect, Rect {x: 200.0, y: 120.0, width: 920.0, height: 436.0});
+    assert_eq!(rect.to_string(), "{{200, 120}, {920, 436}}");
+    assert_eq!(Rect::parse("{{1.5,2},{3,4}}").map(|r| r.x), Some(1.5));
+    assert_eq!(Rect::parse("{{1, 2}, {3}}"), None);
+    assert_eq!(Rect::parse("1, 2, 3, 4"), None);
+}
+
+#[test]
+fn browser_window_settings() {
+    let plist = Plist::Dictionary(vec![
+        entry("ContainerShowSidebar", Plist::Boolean(true)),
+        entry("ShowPathbar", Plist::Boolean(false)),
+        entry("ShowSidebar", Plist::Boolean(true)),
+        entry("ShowStatusBar", Plist::Boolean(false)),
+        entry("ShowTabView", Plist::Boolean(false)),
+        entry("ShowToolbar", Plist::Boolean(true)),
+        entry("SidebarWidth", Plist::Integer(180)),
+        entry("WindowBounds", Plist::String("{{200, 120}, {920, 436}}".to_string())),
+    ]);
+    let settings = BrowserWindowSettings::from_plist(&plist).expect("Could not read the settings.");
+    assert_eq!(settings.window_bounds, Some(Rect {x: 200.0, y: 120.0, width: 920.0, height: 436.0}));
+    assert_eq!(settings.sidebar_width, Some(180));
+    assert_eq!(settings.show_sidebar, Some(true));
+    assert_eq!(settings.show_toolbar, Some(true));
+    assert_eq!(settings.show_status_bar, Some(false));
+    assert_eq!(settings.show_pathbar, Some(false));
+    assert_eq!(settings.show_tab_view, Some(false));
+    assert_eq!(settings.other, vec![entry("ContainerShowSidebar", Plist::Boolean(true))]);
+    assert_eq!(settings.to_plist(), plist);
+}
+
+#[test]
+fn unexpected_types_are_kept() {
+    let plist = Plist::Dictionary(vec![
+        entry("SidebarWidth", Plist::Real(180.5)),
+        entry("WindowBounds", Plist::String("somewhere".to_string())),
+    ]);
+    let settings = BrowserWindowSettings::from_plist(&plist).expect("Could not read the settings.");
+    assert_eq!(settings.sidebar_width, None);
+    assert_eq!(settings.window_bounds, None);
+    assert_eq!(settings.to_plist(), plist);
+
+    assert!(BrowserWindowSettings::from_plist(&Plist::Integer(1)).is_err());
+}
+
+#[test]
+fn browser_window_settings_in_a_store() {
+    let settings = BrowserWindowSettings {
+        window_bounds: Some(Rect {x: 10.0, y: 20.0, width: 640.0, height: 480.0}),
+        show_sidebar: Some(false),
+        ..Default::default()
+    };
+    let mut store = DsStore::default();
+    store.set_browser_window_settings(".", &settings);
+    let written = store.to_bytes().expect("Could not write the DS_Store.");
+    let reread = DsStore::new(&written).expect("Could not construct the DS_Store.");
+    assert_eq!(reread.browser_window_settings("."), Some(settings));
+    assert_eq!(reread.browser_window_settings("missing"), None);
+}
+
+/// The "icvp" of an installer disk image, as Finder writes it.
+fn installer_icvp() -> Plist {
+    Plist::Dictionary(vec![
+        entry("backgroundColorBlue", Plist::Real(1.0)),
+        entry("backgroundColorGreen", Plist::Real(1.0)),
+        entry("backgroundColorRed", Plist::Real(1.0)),
+        entry("backgroundImageAlias", Plist::Data(vec![0, 0, 0, 0, 1, 2, 0, 0])),
+        entry("backgroundType", Plist::Integer(2)),
+        entry("gridOffsetX", Plist::Real(0.0)),
+        entry("gridOffsetY", Plist::Real(0.0)),
+        entry("gridSpacing", Plist::Real(100.0)),
+        entry("iconSize", Plist::Real(128.0)),
+        entry("labelOnBottom", Plist::Boolean(true)),
+        entry("showIconPreview", Plist::Boolean(true)),
+        entry("showItemInfo", Plist::Boolean(false)),
+        entry("textSize", Plist::Real(13.0)),
+        entry("viewOptionsVersion", Plist::Integer(1)),
+        entry("arrangeBy", Plist::String("none".to_string())),
+    ])
+}
+
+#[test]
+fn icon_view_options() {
+    let plist = installer_icvp();
+    let options = IconViewOptions::from_plist(&plist).expect("Could not read the options.");
+    assert_eq!(options.icon_size, Some(128.0));
+    assert_eq!(options.text_size, Some(13.0));
+    assert_eq!(options.label_position, Some(LabelPosition::Bottom));
+    assert_eq!(options.grid_spacing, Some(100.0));
+    assert_eq!(options.grid_offset_x, Some(0.0));
+    assert_eq!(options.grid_offset_y, Some(0.0));
+    assert_eq!(options.arrange_by, Some(Arrangement::None));
+    assert_eq!(options.show_icon_preview, Some(true));
+    assert_eq!(options.show_item_info, Some(false));
+    assert_eq!(options.background_type, Some(IconViewBackground::Picture));
+    assert_eq!(options.background_color_red, Some(1.0));
+    assert_eq!(options.background_image_alias, Some(vec![0, 0, 0, 0, 1, 2, 0, 0]));
+    assert_eq!(options.other, vec![entry("viewOptionsVersion", Plist::Integer(1))]);
+    assert_eq!(options.to_plist(), plist);
+}
+
+#[test]
+fn unknown_arrangement_is_kept() {
+    let plist = Plist::Dictionary(vec![
+        entry("arrangeBy", Plist::String("tags".to_string())),
+        entry("labelOnBottom", Plist::Boolean(false)),
+        entry("backgroundType", Plist::Integer(7)),
+        entry("iconSize", Plist::Integer(64)),
+    ]);
+    let options = IconViewOptions::from_plist(&plist).expect("Could not read the options.");
+    assert_eq!(options.arrange_by, Some(Arrangement::Other("tags".to_string())));
+    assert_eq!(options.label_position, Some(LabelPosition::Right));
+    assert_eq!(options.background_type, Some(IconViewBackground::Other(7)));
+    assert_eq!(options.icon_size, None);
+    assert_eq!(options.to_plist(), plist);
+}
+
+#[test]
+fn typed_fields_replace_entries_of_the_same_key() {
+    let plist = Plist::Dictionary(vec![
+        entry("viewOptionsVersion", Plist::Integer(1)),
+        entry("iconSize", Plist::Integer(64)),
+        entry("textSize", Plist::Real(12.0)),
+    ]);
+    let mut options = IconViewOptions::from_plist(&plist).expect("Could not read the options.");
+    assert_eq!(options.icon_size, None);
+    options.icon_size = Some(96.0);
+    options.text_size = None;
+    options.show_item_info = Some(true);
+    assert_eq!(options.to_plist(), Plist::Dictionary(vec![
+        entry("viewOptionsVersion", Plist::Integer(1)),
+        entry("iconSize", Plist::Real(96.0)),
+        entry("showItemInfo", Plist::Boolean(true)),
+    ]));
+
+    // Without an order to keep, typed fields come first.
+    let options = IconViewOptions {
+        icon_size: Some(32.0),
+        other: vec![entry("iconSize", Plist::Integer(64)), entry("viewOptionsVersion", Plist::Integer(1))],
+        ..Default::default()
+    };
+    assert_eq!(options.to_plist(), Plist::Dictionary(vec![
+        entry("iconSize", Plist::Real(32.0)),
+        entry("viewOptionsVersion", Plist::Integer(1)),
+    ]));
+}
+
+#[test]
+fn icon_view_options_in_a_store() {
+    let options = IconViewOptions {
+        icon_size: Some(96.0),
+        arrange_by: Some(Arrangement::Grid),
+        background_type: Some(IconViewBackground::Color),
+        background_color_red: Some(0.5),
+        background_color_green: Some(0.25),
+        background_color_blue: Some(0.0),
+        ..Default::default()
+    };
+    let mut store = DsStore::default();
+    store.set_icon_view_options(".", &options);
+    let written = store.to_bytes().expect("Could not write the DS_Store.");
+    let reread = DsStore::new(&written).expect("Could not construct the DS_Store.");
+    assert_eq!(reread.icon_view_options("."), Some(options));
+}
+
+fn column_entries(width: i64, ascending: bool, index: i64, visible: bool) -> Vec<(String, Plist)> {
+    vec![
+        entry("width", Plist::Integer(width)),
+        entry("ascending", Plist::Boolean(ascending)),
+        entry("index", Plist::Integer(index)),
+        entry("visible", Plist::Boolean(visible)),
+    ]
+}
+
+#[test]
+fn list_view_settings_with_column_dictionary() {
+    let plist = Plist::Dictionary(vec![
+        entry("sortColumn", Plist::String("dateModified".to_string())),
+        entry("textSize", Plist::Real(12.0)),
+        entry("iconSize", Plist::Real(16.0)),
+        entry("calculateAllSizes", Plist::Boolean(false)),
+        entry("useRelativeDates", Plist::Boolean(true)),
+        entry("showIconPreview", Plist::Boolean(true)),
+        entry("columns", Plist::Dictionary(vec![
+            entry("name", Plist::Dictionary(column_entries(300, true, 0, true))),
+            entry("dateModified", Plist::Dictionary(column_entries(181, false, 1, true))),
+            entry("comments", Plist::Dictionary(column_entries(300, true, 7, false))),
+        ])),
+    ]);
+    let settings = ListViewSettings::from_plist(&plist).expect("Could not read the settings.");
+    assert_eq!(settings.sort_column, Some("dateModified".to_string()));
+    assert_eq!(settings.text_size, Some(12.0));
+    assert_eq!(settings.icon_size, Some(16.0));
+    assert_eq!(settings.calculate_all_sizes, Some(false));
+    assert_eq!(settings.use_relative_dates, Some(true));
+    assert_eq!(settings.other, vec![entry("showIconPreview", Plist::Boolean(true))]);
+
+    let (layout, ref columns) = *settings.columns.as_ref().expect("No columns.");
+    assert_eq!(layout, ColumnLayout::Dictionary);
+    assert_eq!(columns.len(), 3);
+    assert_eq!(columns[1], Column {
+        identifier: "dateModified".to_string(),
+        width: Some(181),
+        ascending: Some(false),
+        index: Some(1),
+        visible: Some(true),
+        ..Default::default()
+    });
+    assert_eq!(columns[2].visible, Some(false));
+    assert_eq!(settings.to_plist(), plist);
+}
+
+#[test]
+fn list_view_settings_with_column_array() {
+    let mut size_column = vec![entry("identifier", Plist::String("size".to_string()))];
+    size_column.extend(column_entries(97, false, 2, true));
+    size_column.push(entry("someday", Plist::Integer(1)));
+    let plist = Plist::Dictionary(vec![
+        entry("sortColumn", Plist::String("name".to_string())),
+        entry("columns", Plist::Array(vec![Plist::Dictionary(size_column)])),
+    ]);
+    let settings = ListViewSettings::from_plist(&plist).expect("Could not read the settings.");
+    let (layout, ref columns) = *settings.columns.as_ref().expect("No columns.");
+    assert_eq!(layout, ColumnLayout::Array);
+    assert_eq!(columns[0].identifier, "size");
+    assert_eq!(columns[0].width, Some(97));
+    assert_eq!(columns[0].other, vec![entry("someday", Plist::Integer(1))]);
+    assert_eq!(settings.to_plist(), plist);
+
+    // Columns without an identifier cannot be typed, so they are kept as they are.
+    let plist = Plist::Dictionary(vec![
+        entry("columns", Plist::Array(vec![Plist::Dictionary(column_entries(1, true, 0, true))])),
+    ]);
+    let settings = ListViewSettings::from_plist(&plist).expect("Could not read the settings.");
+    assert_eq!(settings.columns, None);
+    assert_eq!(settings.to_plist(), plist);
+}
+
+#[test]
+fn list_view_settings_in_a_store() {
+    let settings = ListViewSettings {
+        sort_column: Some("kind".to_string()),
+        columns: Some((ColumnLayout::Array, vec![Column {
+            identifier: "kind".to_string(),
+            width: Some(120),
+            visible: Some(true),
+            ..Default::default()
+        }])),
+        ..Default::default()
+    };
+    let mut store = DsStore::default();
+    store.set_list_view_settings(".", "lsvP", &settings).unwrap();
+    assert!(store.set_list_view_settings(".", "icvp", &settings).is_err());
+    let written = store.to_bytes().expect("Could not write the DS_Store.");
+    let reread = DsStore::new(&written).expect("Could not construct the DS_Store.");
+    assert_eq!(reread.list_view_settings(".", "lsvP"), Some(settings));
+    assert_eq!(reread.list_view_settings(".", "lsvp"), None);
+}
+
+#[test]
+fn equality_ignores_key_order() {
+    let entries = vec![
+        entry("ShowSidebar", Plist::Boolean(true)),
+        entry("SidebarWidth", Plist::Integer(192)),
+        entry("Unknown", Plist::Integer(1)),
+    ];
+    let mut reordered = entries.clone();
+    reordered.swap(0, 1);
+    let settings = BrowserWindowSettings::from_plist(&Plist::Dictionary(entries)).unwrap();
+    let same = BrowserWindowSettings::from_plist(&Plist::Dictionary(reordered)).unwrap();
+    assert_eq!(settings, same);
+    assert_ne!(settings.key_order, same.key_order);
+    assert_ne!(settings.to_plist(), same.to_plist());
+
+    let different = BrowserWindowSettings {sidebar_width: Some(200), ..same.clone()};
+    assert_ne!(settings, different);
+    let different = BrowserWindowSettings {other: vec![], ..same};
+    assert_ne!(settings, different);
+}
@@ -0,0 +1,128 @@
+extern crate ds_store;
+
+mod common;
+
+use ds_store::{DsStore, RecordValue};
+use ds_store::allocator::{Allocator, RecordAction, StyleType, Visitor};
+use common::{file_name, synthetic_store};
+
+/// `common::deep_store`, with a view style for every tenth file.
+fn deep_store() -> Vec<u8> {
+    let mut store = synthetic_store(1500);
+    for i in (0..1500).step_by(10) {
+        store.set_record(&file_name(i), "vstl", RecordValue::Style(StyleType::List)).unwrap();
+    }
+    store.to_bytes().expect("Could not write the store.")
+}
+
+/// Keeps the comments, and counts everything else.
+#[derive(Default)]
+struct Comments {
+    depth: usize,
+    max_depth: usize,
+    nodes: usize,
+    keys: usize,
+    comments: Vec<(String, String)>,
+    other_values: usize,
+}
+
+impl<'a> Visitor<'a> for Comments {
+    fn enter_node(&mut self, _block_id: u32, _leaf: bool, _records: u32) {
+        self.depth += 1;
+        self.max_depth = self.max_depth.max(self.depth);
+        self.nodes += 1;
+    }
+
+    fn exit_node(&mut self, _block_id: u32) {
+        self.depth -= 1;
+    }
+
+    fn record_key(&mut self, _file_name: &str, code: &'a str) -> RecordAction {
+        self.keys += 1;
+        if code == "cmmt" { RecordAction::Decode } else { RecordAction::Skip }
+    }
+
+    fn record_value(&mut self, file_name: &str, _code: &'a str, value: RecordValue<'a>) {
+        match value {
+            RecordValue::String(comment) => self.comments.push((file_name.to_string(), comment)),
+            _ => self.other_values += 1,
+        }
+    }
+}
+
+#[test]
+fn visits_only_the_requested_codes() {
+    let file = deep_store();
+    let allocator = Allocator::new(&file).expect("Could not read the allocator.");
+    let directory = allocator.traverse().expect("Could not traverse the B-Tree.");
+    let mut visitor = Comments::default();
+    allocator.visit(&mut visitor).expect("Could not visit the B-Tree.");
+
+    assert_eq!(visitor.depth, 0);
+    assert_eq!(visitor.max_depth as u32, directory.num_internals + 1);
+    assert_eq!(visitor.nodes as u32, directory.num_nodes);
+    assert_eq!(visitor.keys as u32, directory.num_records);
+    assert_eq!(visitor.other_values, 0);
+    let expected: Vec<(String, String)> = (0..1500).map(|i| (file_name(i), format!("comment {}", i))).collect();
+    assert_eq!(visitor.comments, expected);
+}
+
+/// Decodes every value, and logs the order of the calls.
+#[derive(Default)]
+struct Log(Vec<String>);
+
+impl<'a> Visitor<'a> for Log {
+    fn enter_node(&mut self, block_id: u32, leaf: bool, records: u32) {
+        self.0.push(format!("enter {} {} {}", block_id, leaf, records));
+    }
+
+    fn exit_node(&mut self, block_id: u32) {
+        self.0.push(format!("exit {}", block_id));
+    }
+
+    fn record_key(&mut self, file_name: &str, code: &'a str) -> RecordAction {
+        self.0.push(format!("key {} {}", file_name, code));
+        RecordAction::Decode
+    }
+
+    fn record_value(&mut self, file_name: &str, code: &'a str, value: RecordValue<'a>) {
+        self.0.push(format!("value {} {} {:?}", file_name, code, value));
+    }
+}
+
+#[test]
+fn visits_a_leaf() {
+    let mut store = DsStore::default();
+    store.set_icon_location("a", 1, 2);
+    store.set_record("b", "vstl", RecordValue::Style(StyleType::Icon)).unwrap();
+    let file = store.to_bytes().expect("Could not write the store.");
+    let allocator = Allocator::new(&file).expect("Could not read the allocator.");
+    let root = allocator.traverse().expect("Could not traverse the B-Tree.").root_node;
+
+    let mut log = Log::default();
+    allocator.visit(&mut log).expect("Could not visit the B-Tree.");
+    assert_eq!(log.0, vec![
+        format!("enter {} true 2", root),
+        "key a Iloc".to_string(),
+        format!("value a Iloc {:?}", store.contents()["a"]["Iloc"]),
+        "key b vstl".to_string(),
+        format!("value b vstl {:?}", RecordValue::Style(StyleType::Icon)),
+        format!("exit {}", root),
+    ]);
+}
+
+#[test]
+fn skipped_values_are_not_decoded() {
+    // A view style no version of Finder writes, which fails to decode.
+    let mut file = deep_store();
+    let position = file.windows(12).position(|window| window == b"vstltypeNlsv").expect("No view style.");
+    file[position + 8..position + 12].copy_from_slice(b"zzzz");
+    assert!(DsStore::new(&file).is_err());
+
+    let allocator = Allocator::new(&file).expect("Could not read the allocator.");
+    let mut visitor = Comments::default();
+    allocator.visit(&mut visitor).expect("Could not visit the B-Tree.");
+    assert_eq!(visitor.comments.len(), 1500);
+
+    assert!(allocator.visit(&mut Log::default()).is_err());
+}
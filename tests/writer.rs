@@ -0,0 +1,90 @@
+extern crate ds_store;
+
+mod common;
+
+use ds_store::{DsStore, RecordValue};
+use ds_store::allocator::{Allocator, StyleType};
+use common::synthetic_store;
+
+/// Writes `store`, and checks that it reads back with the same records.
+fn write_and_read_back(store: &DsStore) -> Vec<u8> {
+    let written = store.to_bytes().expect("Could not write the store.");
+    let read = DsStore::new(&written).expect("Could not read the written store.");
+    assert_eq!(format!("{:?}", read.contents()), format!("{:?}", store.contents()));
+    written
+}
+
+/// Checks the prelude, the table of contents and the free list of a written store.
+fn check_layout(written: &[u8]) {
+    assert_eq!(&written[..8], b"\x00\x00\x00\x01Bud1");
+    let allocator = Allocator::new(written).expect("Could not read the allocator.");
+    assert_eq!(allocator.prelude_unknown, &[0; 16][..]);
+    assert_eq!(allocator.offsets_unknown, 0);
+    // Block 0 is the info block the prelude points to.
+    assert_eq!(allocator.offsets[0] & !0x1f, allocator.info_block_offset);
+    assert_eq!(1 << (allocator.offsets[0] & 0x1f), allocator.info_block_size);
+    assert!(allocator.dsdb_location != 0 && (allocator.dsdb_location as usize) < allocator.offsets.len());
+    assert_eq!(allocator.free_list.len(), 32);
+
+    // The prelude, the allocated blocks and the free blocks cover the whole address space, without overlapping.
+    let mut blocks: Vec<(u32, u32)> = vec![(0, 32)];
+    blocks.extend(allocator.offsets.iter().map(|&address| (address & !0x1f, 1 << (address & 0x1f))));
+    for (width, list) in allocator.free_list.iter().enumerate() {
+        let mut sorted = list.clone();
+        sorted.sort();
+        assert_eq!(&sorted, list, "free list {} is not sorted", width);
+        for &offset in list {
+            assert_eq!(offset % (1 << width), 0, "free block {} is not aligned to its size", offset);
+            blocks.push((offset, 1 << width));
+        }
+    }
+    blocks.sort();
+    let mut end: u64 = 0;
+    for (offset, size) in blocks {
+        assert_eq!(offset as u64, end, "blocks overlap or leave a gap at {}", end);
+        end += size as u64;
+    }
+    assert_eq!(end, 1 << 31);
+    assert_eq!(written.len() as u64, 4 + allocator.offsets.iter()
+        .map(|&address| (address & !0x1f) as u64 + (1 << (address & 0x1f)))
+        .max().unwrap());
+}
+
+#[test]
+fn writes_an_empty_store() {
+    check_layout(&write_and_read_back(&DsStore::default()));
+}
+
+#[test]
+fn writes_a_store() {
+    let mut store = DsStore::default();
+    store.set_icon_location("file.txt", 10, 20);
+    store.set_record(".", "vstl", RecordValue::Style(StyleType::Icon)).unwrap();
+    store.set_record("file.txt", "cmmt", RecordValue::String("A comment".to_string())).unwrap();
+    check_layout(&write_and_read_back(&store));
+}
+
+#[test]
+fn writes_a_deep_store() {
+    check_layout(&write_and_read_back(&synthetic_store(1500)));
+}
+
+#[test]
+fn splits_large_last_records() {
+    // Only one of the last two records fits in a node, and two small records fill the first node.
+    let mut store = DsStore::default();
+    for &(file_name, length) in &[("a", 10), ("b", 10), ("c", 2020), ("d", 1100), ("e", 1100)] {
+        store.set_record(file_name, "cmmt", RecordValue::String("x".repeat(length))).unwrap();
+    }
+    let written = write_and_read_back(&store);
+    check_layout(&written);
+
+    let directory = Allocator::new(&written).unwrap().traverse().expect("Could not traverse the B-Tree.");
+    assert_eq!(directory.num_internals, 1);
+    assert_eq!(directory.num_nodes, 4);
+    let leaves: Vec<Vec<&str>> = directory.nodes.iter()
+        .filter(|node| node.rightmost_child == 0)
+        .map(|node| node.records.iter().map(|(_, file_name, _, _)| file_name.as_str()).collect())
+        .collect();
+    assert_eq!(leaves, vec![vec!["a"], vec!["c"], vec!["e"]]);
+}